@@ -1,5 +1,8 @@
 use clap::{command, crate_authors, Arg, ArgMatches, Command, ArgAction};
 
+use crate::config::{AUTO_CIPHER, CIPHER_NAMES};
+use crate::service::{parse_backoff, DEFAULT_MANAGER, MANAGER_NAMES};
+
 /// Matches the CLI arguments and returns an object containing the values.
 pub fn match_cli_arguments() -> ArgMatches {
     command!()
@@ -9,6 +12,42 @@ pub fn match_cli_arguments() -> ArgMatches {
         .arg_required_else_help(true)
         .about("Released under the MIT license.\n\nUseful utilities on a Samsung MagicINFO server for sysadmin tasks.")
         .author(crate_authors!("\n"))
+        .arg(Arg::new("reveal")
+            .global(true)
+            .action(ArgAction::SetTrue)
+            .help("Prints the values of passwords, keys, secrets and tokens instead of masking them")
+            .long("reveal")
+        )
+        .arg(Arg::new("instance")
+            .global(true)
+            .num_args(1)
+            .env("MAGICUTIL_INSTANCE")
+            .help("The profile in the instances file of the MagicINFO installation to work on, which sets its service name, home directory and probe")
+            .long("instance")
+        )
+        .arg(Arg::new("instances-file")
+            .global(true)
+            .num_args(1)
+            .env("MAGICUTIL_INSTANCES")
+            .help("A toml, yaml or json file with instance profiles. Defaults to magicutil-instances.toml next to magicutil")
+            .long("instances-file")
+        )
+        .arg(Arg::new("service-name")
+            .global(true)
+            .num_args(1)
+            .env("MAGICUTIL_SERVICE_NAME")
+            .help("The name of the service that runs MagicINFO, instead of MagicInfoPremium or the one of the instance")
+            .long("service-name")
+        )
+        .arg(Arg::new("service-manager")
+            .global(true)
+            .num_args(1)
+            .value_parser(MANAGER_NAMES.to_vec())
+            .env("MAGICUTIL_SERVICE_MANAGER")
+            .default_value(DEFAULT_MANAGER)
+            .help("How the MagicINFO service is managed: scm for the Windows service control manager, systemd for a systemd unit, or mock for a scripted service that goes through the states in MAGICUTIL_SERVICE_MOCK")
+            .long("service-manager")
+        )
         .subcommand(Command::new("system")
             .subcommand_required(true)
             .arg_required_else_help(true)
@@ -59,6 +98,18 @@ pub fn match_cli_arguments() -> ArgMatches {
                 .action(ArgAction::SetTrue)
                     .help("Setting this value returns the property values as json")
                     .long("json")
+                )
+                .arg(Arg::new("check")
+                    .action(ArgAction::SetTrue)
+                    .help("Checks whether the database accepts connections and whether wsrm.dbVendor matches the url")
+                    .long("check")
+                )
+                .arg(Arg::new("timeout")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .default_value("5")
+                    .help("The number of seconds to wait for a connection with --check")
+                    .long("timeout")
                 ))
             .subcommand(Command::new("service")
                 .about("Returns information about MagicINFO service")
@@ -85,7 +136,70 @@ pub fn match_cli_arguments() -> ArgMatches {
         .subcommand(Command::new("service")
         .subcommand_required(true)
             .arg_required_else_help(true)
-            .about("Utilities based on the MagicINFO Windows service")
+            .about("Utilities based on the MagicINFO service")
+            .arg(Arg::new("timeout")
+                .global(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64))
+                .env("MAGICUTIL_SERVICE_TIMEOUT")
+                .help("The number of seconds start, stop, restart and wait may take before they exit with exit code 4. Waits forever if not set or 0")
+                .long("timeout")
+            )
+            .arg(Arg::new("interval")
+                .global(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(10..))
+                .env("MAGICUTIL_SERVICE_INTERVAL")
+                .default_value("1000")
+                .help("The number of milliseconds between checks of the service state or availability")
+                .long("interval")
+            )
+            .arg(Arg::new("backoff")
+                .global(true)
+                .num_args(1)
+                .value_parser(parse_backoff)
+                .env("MAGICUTIL_SERVICE_BACKOFF")
+                .default_value("1")
+                .help("The factor the interval is multiplied with after every check, like 2 to double it. 1 keeps the interval the same")
+                .long("backoff")
+            )
+            .arg(Arg::new("max-interval")
+                .global(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u64).range(10..))
+                .env("MAGICUTIL_SERVICE_MAX_INTERVAL")
+                .default_value("30000")
+                .help("The number of milliseconds the interval grows to at most with --backoff")
+                .long("max-interval")
+            )
+            .arg(Arg::new("probe")
+                .global(true)
+                .num_args(1)
+                .env("MAGICUTIL_PROBE")
+                .help("The http or https url requested to check whether MagicINFO is available. Defaults to the probe of the instance, or a url derived from web_url or listen.port in config.properties or from the Tomcat connectors")
+                .long("probe")
+            )
+            .arg(Arg::new("probe-path")
+                .global(true)
+                .num_args(1)
+                .help("The path requested to check whether MagicINFO is available, instead of /MagicInfo/openapi/auth?cmd=isMagicInfo")
+                .long("probe-path")
+            )
+            .arg(Arg::new("expect-status")
+                .global(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u16).range(100..600))
+                .value_delimiter(',')
+                .default_value("200")
+                .help("The response status, or comma-separated statuses, that mean MagicINFO is available")
+                .long("expect-status")
+            )
+            .arg(Arg::new("insecure")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Skips the verification of the certificate of an https probe, like a self-signed certificate")
+                .long("insecure")
+            )
             .subcommand(Command::new("status")
                 .about("Returns the current service status")
                 .arg(Arg::new("json")
@@ -149,12 +263,50 @@ pub fn match_cli_arguments() -> ArgMatches {
                     .help("Waits until the MagicINFO service is available and serving via HTTP.")
                     .long("available")
                 )
+                .arg(Arg::new("silent")
+                .action(ArgAction::SetTrue)
+                    .help("Disables output to stdout")
+                    .long("silent")
+                )
             )
         )
         .subcommand(Command::new("config")
             .subcommand_required(true)
             .arg_required_else_help(true)
             .about("Get, set, replace or remove properties from MagicINFO's main config.properties file")
+            .arg(Arg::new("backups")
+                .global(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(usize))
+                .env("MAGICUTIL_CONFIG_BACKUPS")
+                .default_value("5")
+                .help("The number of timestamped backups of the config.properties file to keep when it is changed")
+                .long("backups")
+            )
+            .arg(Arg::new("cipher")
+                .global(true)
+                .num_args(1)
+                .value_parser([&[AUTO_CIPHER], CIPHER_NAMES].concat())
+                .env("MAGICUTIL_CIPHER")
+                .default_value(AUTO_CIPHER)
+                .help("The cipher used to encrypt and decrypt values. With auto, values are decrypted with the cipher that matches them, like ENC(...) for jasypt, and encrypted with aes-ecb")
+                .long("cipher")
+            )
+            .arg(Arg::new("key-version")
+                .global(true)
+                .num_args(1)
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .env("MAGICUTIL_KEY_VERSION")
+                .help("The version N of the encrypt.manager.key.vN key to use. By default values are decrypted with the key version that matches them and encrypted with the newest key")
+                .long("key-version")
+            )
+            .arg(Arg::new("schema")
+                .global(true)
+                .num_args(1)
+                .env("MAGICUTIL_CONFIG_SCHEMA")
+                .help("A toml, yaml or json file with rules for keys, which extends or replaces the built-in schema")
+                .long("schema")
+            )
             .subcommand(Command::new("get")
                 .about("Returns one or more configuration properties")
                 .arg(Arg::new("PROPERTY")
@@ -172,21 +324,165 @@ pub fn match_cli_arguments() -> ArgMatches {
                     .long("decrypt")
                 )
             )
-            .subcommand(Command::new("set")
-                .about("Sets a configuration property")
-                .arg(Arg::new("KEY")
+            .subcommand(Command::new("list")
+                .about("Lists the configuration properties, optionally filtered by key")
+                .arg(Arg::new("PATTERN")
+                    .num_args(1..)
+                    .help("Glob patterns the keys should match, like device.log4j.*")
+                )
+                .arg(Arg::new("prefix")
                     .num_args(1)
-                    .required(true)
+                    .help("Only lists keys starting with this prefix")
+                    .long("prefix")
                 )
-                .arg(Arg::new("VALUE")
+                .arg(Arg::new("decrypt")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this flag will try to decrypt the encrypted values")
+                    .long("decrypt")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value returns the properties as json")
+                    .long("json")
+                )
+                .arg(Arg::new("show-line-numbers")
+                    .action(ArgAction::SetTrue)
+                    .help("Shows the line number each property is defined on")
+                    .long("show-line-numbers")
+                )
+            )
+            .subcommand(Command::new("search")
+                .about("Searches for configuration properties with keys or values matching a regex")
+                .arg(Arg::new("PATTERN")
+                    .num_args(1)
+                    .help("A regex that either the key or the value should match")
+                )
+                .arg(Arg::new("key")
+                    .num_args(1)
+                    .help("A regex the key should match")
+                    .long("key")
+                )
+                .arg(Arg::new("value")
+                    .num_args(1)
+                    .help("A regex the value should match")
+                    .long("value")
+                )
+                .arg(Arg::new("decrypt")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this flag will try to decrypt the encrypted values")
+                    .long("decrypt")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value returns the properties as json")
+                    .long("json")
+                )
+                .arg(Arg::new("show-line-numbers")
+                    .action(ArgAction::SetTrue)
+                    .help("Shows the line number each property is defined on")
+                    .long("show-line-numbers")
+                )
+            )
+            .subcommand(Command::new("export")
+                .about("Exports the configuration properties as json, yaml, toml or dotenv file")
+                .arg(Arg::new("format")
+                    .num_args(1)
+                    .value_parser(["json", "yaml", "toml", "env"])
+                    .default_value("json")
+                    .help("The format to export to")
+                    .long("format")
+                )
+                .arg(Arg::new("nested")
+                    .action(ArgAction::SetTrue)
+                    .help("Nests the keys by their dotted segments. A key that also has nested keys stores its value under '_'")
+                    .long("nested")
+                )
+                .arg(Arg::new("decrypt")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this flag will try to decrypt the encrypted values")
+                    .long("decrypt")
+                )
+                .arg(Arg::new("output")
+                    .num_args(1)
+                    .help("Writes the export to the given file instead of stdout")
+                    .long("output")
+                    .short('o')
+                )
+            )
+            .subcommand(Command::new("import")
+                .about("Applies the keys and values of a json, yaml, toml or dotenv file to the configuration properties")
+                .arg(Arg::new("FILE")
                     .num_args(1)
                     .required(true)
+                    .help("The file to import, or - to read from stdin. Keys with a null or !unset value are removed")
+                )
+                .arg(Arg::new("format")
+                    .num_args(1)
+                    .value_parser(["json", "yaml", "toml", "env"])
+                    .help("The format of the file. Defaults to the format matching the file extension")
+                    .long("format")
+                )
+                .arg(Arg::new("encrypt-keys")
+                    .num_args(1..)
+                    .value_delimiter(',')
+                    .help("Keys whose values are encrypted before they are written")
+                    .long("encrypt-keys")
+                )
+                .arg(Arg::new("interpolate")
+                    .action(ArgAction::SetTrue)
+                    .help("Replaces ${...} references in the values like 'config set'. By default values are written exactly as they are in the file")
+                    .long("interpolate")
+                )
+                .arg(Arg::new("allow-unresolved")
+                    .action(ArgAction::SetTrue)
+                    .requires("interpolate")
+                    .help("Leaves ${...} references that can't be resolved in the value instead of failing")
+                    .long("allow-unresolved")
+                )
+                .arg(Arg::new("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Writes the values even if they don't match the schema")
+                    .long("force")
+                )
+            )
+            .subcommand(Command::new("set")
+                .about("Sets one or more configuration properties. ${NAME}, ${NAME:-default} and ${NAME:?error} in the values are replaced with environment variables or other properties, $${ is a literal ${")
+                .arg(Arg::new("ASSIGNMENT")
+                    .num_args(0..)
+                    .help("Either a single KEY VALUE pair, or any number of KEY=VALUE assignments")
+                )
+                .arg(Arg::new("from-file")
+                    .num_args(1)
+                    .help("A properties file with the values to set. Keys with the value !unset are removed")
+                    .long("from-file")
+                )
+                .arg(Arg::new("remove")
+                    .num_args(1)
+                    .action(ArgAction::Append)
+                    .help("A key to remove. Can be given multiple times")
+                    .long("remove")
                 )
                 .arg(Arg::new("encrypt")
                     .action(ArgAction::SetTrue)
-                    .help("Setting this flag will encrypt the value before writing it to the config.properties file")
+                    .help("Setting this flag will encrypt the values before writing them to the config.properties file")
                     .long("encrypt")
                 )
+                .arg(Arg::new("encrypt-keys")
+                    .num_args(1..)
+                    .value_delimiter(',')
+                    .help("Keys whose values are encrypted before they are written")
+                    .long("encrypt-keys")
+                )
+                .arg(Arg::new("allow-unresolved")
+                    .action(ArgAction::SetTrue)
+                    .help("Leaves ${...} references that can't be resolved in the value instead of failing")
+                    .long("allow-unresolved")
+                )
+                .arg(Arg::new("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Writes the values even if they don't match the schema")
+                    .long("force")
+                )
             )
             .subcommand(Command::new("remove")
                 .about("Removes a configuration property")
@@ -194,6 +490,11 @@ pub fn match_cli_arguments() -> ArgMatches {
                     .num_args(1)
                     .required(true)
                 )
+                .arg(Arg::new("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Removes the key even if the schema requires it")
+                    .long("force")
+                )
             )
             .subcommand(Command::new("replace")
                 .about("Changes a configuration property by replacing part of the existing value")
@@ -209,16 +510,145 @@ pub fn match_cli_arguments() -> ArgMatches {
                 .num_args(1)
                 .required(true)
                 )
+                .arg(Arg::new("allow-unresolved")
+                    .action(ArgAction::SetTrue)
+                    .help("Leaves ${...} references that can't be resolved in the value instead of failing")
+                    .long("allow-unresolved")
+                )
+                .arg(Arg::new("force")
+                    .action(ArgAction::SetTrue)
+                    .help("Writes the value even if it doesn't match the schema")
+                    .long("force")
+                )
             )
             .subcommand(Command::new("overlay")
-                .about("Outputs a configuration file constructed from the base properties-file with values applied from the overlay properties-file")
+                .about("Outputs a configuration file constructed from the base properties-file with values applied from the overlay properties-files")
                 .arg(Arg::new("BASE_CONFIG_INI")
                 .num_args(1)
                 .required(true)
                 )
                 .arg(Arg::new("OVERLAY_CONFIG_INI")
-                .num_args(1)
+                .num_args(1..)
                 .required(true)
+                .help("One or more overlay properties-files, applied in order. Use - to read an overlay from stdin. A key with the value !unset is removed")
+                )
+                .arg(Arg::new("in-place")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("output")
+                    .help("Writes the result to the base properties-file, after making a backup of it")
+                    .long("in-place")
+                )
+                .arg(Arg::new("output")
+                    .num_args(1)
+                    .help("Writes the result to the given file instead of stdout")
+                    .long("output")
+                    .short('o')
+                )
+                .arg(Arg::new("report")
+                    .action(ArgAction::SetTrue)
+                    .help("Prints which overlay set or removed each key")
+                    .long("report")
+                )
+                .arg(Arg::new("allow-unresolved")
+                    .action(ArgAction::SetTrue)
+                    .help("Leaves ${...} references that can't be resolved in the value instead of failing")
+                    .long("allow-unresolved")
+                )
+            )
+            .subcommand(Command::new("lint")
+                .about("Checks a properties file for duplicate, malformed and suspicious entries")
+                .arg(Arg::new("FILE")
+                    .num_args(1)
+                    .help("The properties file to check. Defaults to MagicINFO's config.properties file")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value returns the problems as json")
+                    .long("json")
+                )
+            )
+            .subcommand(Command::new("watch")
+                .about("Watches a properties file and prints every change to a key as it happens")
+                .arg(Arg::new("FILE")
+                    .num_args(1)
+                    .help("The properties file to watch. Defaults to MagicINFO's config.properties file")
+                )
+                .arg(Arg::new("interval")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(u64).range(10..))
+                    .default_value("1000")
+                    .help("The number of milliseconds between checks for changes")
+                    .long("interval")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value prints every change as a json line")
+                    .long("json")
+                )
+            )
+            .subcommand(Command::new("validate")
+                .about("Checks the values of well-known keys against the schema, like port numbers, urls and log levels")
+                .arg(Arg::new("FILE")
+                    .num_args(1)
+                    .help("The properties file to check. Defaults to MagicINFO's config.properties file")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value returns the problems as json")
+                    .long("json")
+                )
+            )
+            .subcommand(Command::new("diff")
+                .about("Compares two properties files key by key, ignoring ordering, comments and formatting")
+                .arg(Arg::new("OLD")
+                    .num_args(1)
+                    .required(true)
+                )
+                .arg(Arg::new("NEW")
+                    .num_args(1)
+                    .help("The file to compare with. Defaults to MagicINFO's config.properties file")
+                )
+                .arg(Arg::new("decrypt")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this flag compares encrypted values after decrypting them")
+                    .long("decrypt")
+                )
+                .arg(Arg::new("format")
+                    .num_args(1)
+                    .value_parser(["text", "unified", "json"])
+                    .default_value("text")
+                    .help("The output format of the differences")
+                    .long("format")
+                )
+            )
+            .subcommand(Command::new("rotate-key")
+                .about("Replaces the encryption key and re-encrypts all encrypted values with the new key")
+                .arg(Arg::new("key")
+                    .num_args(1)
+                    .help("The new encryption key, of at least 16 characters. A random key is generated if omitted")
+                    .long("key")
+                )
+                .arg(Arg::new("dry-run")
+                    .action(ArgAction::SetTrue)
+                    .help("Shows which values would be re-encrypted without changing anything")
+                    .long("dry-run")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value returns the report as json")
+                    .long("json")
+                )
+            )
+            .subcommand(Command::new("restore")
+                .about("Lists the backups of the config.properties file or restores one of them")
+                .arg(Arg::new("BACKUP")
+                    .num_args(1)
+                    .help("The number or path of the backup to restore. Lists all backups if omitted")
+                )
+                .arg(Arg::new("json")
+                    .action(ArgAction::SetTrue)
+                    .help("Setting this value returns the list of backups as json")
+                    .long("json")
                 )
             )
         )
@@ -259,5 +689,84 @@ pub fn match_cli_arguments() -> ArgMatches {
                 )
             )
         )
+        .subcommand(Command::new("crypto")
+            .arg_required_else_help(true)
+            .subcommand_required(true)
+            .about("Encrypts or decrypts values like MagicINFO does in its config.properties file, without needing the installation")
+            .arg(Arg::new("cipher")
+                .global(true)
+                .num_args(1)
+                .value_parser([&[AUTO_CIPHER], CIPHER_NAMES].concat())
+                .env("MAGICUTIL_CIPHER")
+                .default_value(AUTO_CIPHER)
+                .help("The cipher used to encrypt and decrypt values. With auto, values are decrypted with the cipher that matches them, like ENC(...) for jasypt, and encrypted with aes-ecb")
+                .long("cipher")
+            )
+            .arg(Arg::new("key")
+                .global(true)
+                .num_args(1)
+                .env("MAGICUTIL_ENCRYPTION_KEY")
+                .hide_env_values(true)
+                .help("The encryption key. Ignored if --key-file or --properties is given")
+                .long("key")
+            )
+            .arg(Arg::new("key-file")
+                .global(true)
+                .num_args(1)
+                .help("A file containing only the encryption key")
+                .long("key-file")
+            )
+            .arg(Arg::new("properties")
+                .global(true)
+                .num_args(1)
+                .help("A properties file containing the encryption key, like a copy of the config.properties file")
+                .long("properties")
+            )
+            .subcommand(Command::new("encrypt")
+                .about("Encrypts the values, or every line of stdin if no values are given")
+                .arg(Arg::new("VALUE")
+                    .num_args(1..)
+                )
+            )
+            .subcommand(Command::new("decrypt")
+                .about("Decrypts the values, or every line of stdin if no values are given")
+                .arg(Arg::new("VALUE")
+                    .num_args(1..)
+                )
+            )
+        )
+        .subcommand(Command::new("db")
+            .arg_required_else_help(true)
+            .subcommand_required(true)
+            .about("Utilities based on MagicINFO's database, using the credentials from the config.properties file")
+            .subcommand(Command::new("query")
+                .about("Runs SQL on the database and prints the returned rows. Only PostgreSQL is supported")
+                .arg(Arg::new("SQL")
+                    .num_args(1)
+                    .conflicts_with("file")
+                    .help("The SQL to run. Multiple statements can be separated with ';'")
+                )
+                .arg(Arg::new("file")
+                    .num_args(1)
+                    .help("A file with the SQL to run, or - to read it from stdin")
+                    .short('f')
+                    .long("file")
+                )
+                .arg(Arg::new("format")
+                    .num_args(1)
+                    .value_parser(["table", "csv", "json"])
+                    .default_value("table")
+                    .help("The format the rows are printed in")
+                    .long("format")
+                )
+                .arg(Arg::new("timeout")
+                    .num_args(1)
+                    .value_parser(clap::value_parser!(u64).range(1..))
+                    .default_value("10")
+                    .help("The number of seconds to wait for a connection")
+                    .long("timeout")
+                )
+            )
+        )
         .get_matches()
 }
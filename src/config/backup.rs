@@ -0,0 +1,274 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use chrono::{Local, NaiveDateTime};
+use simple_error::SimpleError;
+
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+
+const BACKUP_EXTENSION: &str = "bak";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+
+/// A timestamped copy of a properties file, stored next to the original.
+#[derive(Debug, Clone, Serialize)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub timestamp: String,
+}
+
+/// Writes the content to the given path without ever leaving a partially
+/// written file behind. The content is written to a temporary file in the
+/// same directory, flushed to disk and then renamed over the original. The
+/// permissions and owner of the original are kept, since it can hold secrets.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), SimpleError> {
+    write_atomic_from(path, content, path)
+}
+
+/// Writes the content atomically like `write_atomic`, with the permissions
+/// and owner of the file at `source_path`. A backup is a new file, so it would
+/// otherwise be readable by everyone who can read the directory.
+fn write_atomic_from(path: &Path, content: &[u8], source_path: &Path) -> Result<(), SimpleError> {
+    let file_name = get_file_name(path)?;
+    let temp_path = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+
+    let write_res = File::create(&temp_path).and_then(|mut file| {
+        copy_permissions(source_path, &temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(e) = write_res {
+        let _ = fs::remove_file(&temp_path);
+        let message = format!("Could not write properties file: {e}");
+        return Err(SimpleError::new(message));
+    }
+
+    if let Err(e) = replace_file(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        let message = format!("Could not replace properties file: {e}");
+        return Err(SimpleError::new(message));
+    }
+
+    // Make sure the rename itself is persisted as well. Directories can't be
+    // opened as a file on Windows, where the rename is durable on its own.
+    #[cfg(unix)]
+    if let Some(directory) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        let _ = File::open(directory).and_then(|directory| directory.sync_all());
+    }
+
+    Ok(())
+}
+
+/// Gives the temporary file the permissions and owner of the source file,
+/// before any content is written to it. Changing the owner is best effort,
+/// since only privileged users can give a file away.
+#[cfg(unix)]
+fn copy_permissions(source_path: &Path, temp_path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{chown, MetadataExt};
+
+    let Ok(metadata) = fs::metadata(source_path) else {
+        return Ok(());
+    };
+
+    let _ = chown(temp_path, Some(metadata.uid()), Some(metadata.gid()));
+    fs::set_permissions(temp_path, metadata.permissions())
+}
+
+/// Gives the temporary file the ACL of the source file, protected from the
+/// ACL of the directory. A file that is replaced keeps its own ACLs through
+/// `replace_file`, but a new file like a backup would inherit them.
+#[cfg(windows)]
+fn copy_permissions(source_path: &Path, temp_path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Security::{GetFileSecurityW, SetFileSecurityW, DACL_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION};
+
+    if !source_path.exists() {
+        return Ok(());
+    }
+
+    let to_wide = |path: &Path| path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let (source, temp) = (to_wide(source_path), to_wide(temp_path));
+
+    // SAFETY: both paths are null terminated, and the buffer is as large as
+    // the first call says the security descriptor is.
+    unsafe {
+        let mut length = 0;
+        GetFileSecurityW(source.as_ptr(), DACL_SECURITY_INFORMATION, std::ptr::null_mut(), 0, &mut length);
+        let mut descriptor = vec![0u8; length as usize];
+        if GetFileSecurityW(source.as_ptr(), DACL_SECURITY_INFORMATION, descriptor.as_mut_ptr().cast(), length, &mut length) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if SetFileSecurityW(temp.as_ptr(), DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION, descriptor.as_mut_ptr().cast()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn copy_permissions(_source_path: &Path, _temp_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn replace_file(temp_path: &Path, path: &Path) -> io::Result<()> {
+    fs::rename(temp_path, path)
+}
+
+/// Replaces the file with ReplaceFileW, which keeps the ACLs, owner and
+/// attributes of the original. A rename would leave the file with the ACLs
+/// inherited from the directory.
+#[cfg(windows)]
+fn replace_file(temp_path: &Path, path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{ReplaceFileW, REPLACEFILE_IGNORE_MERGE_ERRORS};
+
+    if !path.exists() {
+        return fs::rename(temp_path, path);
+    }
+
+    let to_wide = |path: &Path| path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let (replaced, replacement) = (to_wide(path), to_wide(temp_path));
+
+    // SAFETY: both paths are null terminated and outlive the call, and the
+    // optional arguments are null.
+    let replaced_ok = unsafe {
+        ReplaceFileW(replaced.as_ptr(), replacement.as_ptr(), std::ptr::null(), REPLACEFILE_IGNORE_MERGE_ERRORS, std::ptr::null(), std::ptr::null())
+    };
+
+    match replaced_ok {
+        0 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Copies the file to a new timestamped backup and removes the oldest backups
+/// so that at most `keep` remain. Returns the path of the new backup, or None
+/// when backups are disabled.
+pub fn create_backup(path: &Path, keep: usize) -> Result<Option<PathBuf>, SimpleError> {
+    if keep == 0 {
+        return Ok(None);
+    }
+
+    let file_name = get_file_name(path)?;
+    let timestamp = Local::now().format(TIMESTAMP_FORMAT);
+    let backup_path = path.with_file_name(format!("{file_name}.{timestamp}.{BACKUP_EXTENSION}"));
+
+    let content = fs::read(path).map_err(|e| {
+        let message = format!("Could not read properties file for backup: {e}");
+        SimpleError::new(message)
+    })?;
+    write_atomic_from(&backup_path, &content, path)?;
+
+    for backup in list_backups(path)?.iter().skip(keep) {
+        if let Err(e) = fs::remove_file(&backup.path) {
+            let message = format!("Could not remove old backup {}: {e}", backup.path.display());
+            return Err(SimpleError::new(message));
+        }
+    }
+
+    Ok(Some(backup_path))
+}
+
+/// Returns all backups of the given file, newest first.
+pub fn list_backups(path: &Path) -> Result<Vec<Backup>, SimpleError> {
+    let file_name = get_file_name(path)?;
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let entries = fs::read_dir(&directory).map_err(|e| {
+        let message = format!("Could not list backups in {}: {e}", directory.display());
+        SimpleError::new(message)
+    })?;
+
+    let prefix = format!("{file_name}.");
+    let suffix = format!(".{BACKUP_EXTENSION}");
+    let mut backups = Vec::new();
+
+    for entry in entries.flatten() {
+        let entry_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(stamp) = entry_name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix(&suffix)) else {
+            continue;
+        };
+
+        let Ok(timestamp) = NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT) else {
+            continue;
+        };
+
+        backups.push(Backup {
+            path: entry.path(),
+            timestamp: timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+        });
+    }
+
+    // The timestamp format sorts chronologically as plain text.
+    backups.sort_by(|a, b| b.path.cmp(&a.path));
+    Ok(backups)
+}
+
+/// Replaces the file with the content of the backup. The current content is
+/// backed up first, so a restore can be undone as well.
+pub fn restore_backup(path: &Path, backup: &Backup, keep: usize) -> Result<(), SimpleError> {
+    let content = fs::read(&backup.path).map_err(|e| {
+        let message = format!("Could not read backup {}: {e}", backup.path.display());
+        SimpleError::new(message)
+    })?;
+
+    create_backup(path, keep.max(1))?;
+    write_atomic(path, &content)
+}
+
+fn get_file_name(path: &Path) -> Result<String, SimpleError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        let message = format!("Invalid properties file path: {}", path.display());
+        SimpleError::new(message)
+    })?;
+
+    Ok(file_name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    #[test]
+    fn test_if_permissions_are_kept() {
+        use std::{env, fs, os::unix::fs::PermissionsExt};
+        use super::write_atomic;
+
+        let path = env::temp_dir().join(format!("magicutil-atomic-{}.properties", std::process::id()));
+        fs::write(&path, "wsrm.password = secret\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let write_res = write_atomic(&path, b"wsrm.password = changed\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(write_res.is_ok());
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(content, "wsrm.password = changed\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_if_backups_get_the_permissions_of_the_file() {
+        use std::{env, fs, os::unix::fs::PermissionsExt};
+        use super::create_backup;
+
+        let directory = env::temp_dir().join(format!("magicutil-backup-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config.properties");
+        fs::write(&path, "wsrm.password = secret\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let backup_path = create_backup(&path, 1).unwrap().unwrap();
+        let mode = fs::metadata(&backup_path).unwrap().permissions().mode();
+        let content = fs::read_to_string(&backup_path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(content, "wsrm.password = secret\n");
+    }
+}
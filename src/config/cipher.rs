@@ -0,0 +1,248 @@
+use base64::{Engine as _, engine::general_purpose};
+use simple_error::SimpleError;
+
+use super::encrypted;
+
+/// The name that selects every cipher, detecting the right one per value.
+pub const AUTO_CIPHER: &str = "auto";
+
+/// The names of the supported ciphers. The first one is used to encrypt
+/// values when the cipher is detected automatically.
+pub const CIPHER_NAMES: &[&str] = &["aes-ecb", "aes-cbc", "jasypt"];
+
+/// A scheme MagicINFO uses to encrypt values in its configuration files.
+pub trait Cipher {
+    fn name(&self) -> &'static str;
+
+    /// Returns whether the value has the form of a ciphertext of this cipher.
+    /// It doesn't tell whether the value decrypts with the key.
+    fn is_candidate(&self, value: &str) -> bool;
+
+    fn encrypt(&self, value: &str) -> Result<String, SimpleError>;
+
+    fn decrypt(&self, value: &str) -> Result<String, SimpleError>;
+}
+
+/// AES-128 in ECB mode with the first 16 bytes of the key, as used for the
+/// values in config.properties.
+struct AesEcb {
+    key: String,
+}
+
+impl Cipher for AesEcb {
+    fn name(&self) -> &'static str {
+        "aes-ecb"
+    }
+
+    fn is_candidate(&self, value: &str) -> bool {
+        decoded_length(value).is_some_and(|length| length > 0 && length % 16 == 0)
+    }
+
+    fn encrypt(&self, value: &str) -> Result<String, SimpleError> {
+        encrypted::aes_128_ecb_encrypt(&self.key, value)
+    }
+
+    fn decrypt(&self, value: &str) -> Result<String, SimpleError> {
+        encrypted::aes_128_ecb_decrypt(&self.key, value)
+    }
+}
+
+/// AES-128 in CBC mode with the first 16 bytes of the key. The value starts
+/// with the IV.
+struct AesCbc {
+    key: String,
+}
+
+impl Cipher for AesCbc {
+    fn name(&self) -> &'static str {
+        "aes-cbc"
+    }
+
+    fn is_candidate(&self, value: &str) -> bool {
+        decoded_length(value).is_some_and(|length| length >= 32 && length % 16 == 0)
+    }
+
+    fn encrypt(&self, value: &str) -> Result<String, SimpleError> {
+        encrypted::aes_128_cbc_encrypt(&self.key, value)
+    }
+
+    fn decrypt(&self, value: &str) -> Result<String, SimpleError> {
+        encrypted::aes_128_cbc_decrypt(&self.key, value)
+    }
+}
+
+/// Jasypt's default PBEWithMD5AndDES, with the value wrapped in `ENC(...)`.
+struct Jasypt {
+    password: String,
+}
+
+impl Cipher for Jasypt {
+    fn name(&self) -> &'static str {
+        "jasypt"
+    }
+
+    fn is_candidate(&self, value: &str) -> bool {
+        unwrap_jasypt(value).is_some()
+    }
+
+    fn encrypt(&self, value: &str) -> Result<String, SimpleError> {
+        encrypted::pbe_md5_des_encrypt(&self.password, value).map(|encrypted| format!("ENC({encrypted})"))
+    }
+
+    fn decrypt(&self, value: &str) -> Result<String, SimpleError> {
+        let Some(wrapped) = unwrap_jasypt(value) else {
+            return Err(SimpleError::new("Expected a value wrapped in ENC(...)"));
+        };
+
+        encrypted::pbe_md5_des_decrypt(&self.password, wrapped)
+    }
+}
+
+/// What a value turned out to be when it was checked with the encryption key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueKind {
+    /// The value is a ciphertext of the key. Contains the decrypted value and
+    /// the name of the cipher.
+    Encrypted(String, &'static str),
+
+    /// The value can't be a ciphertext, because it doesn't have the form of
+    /// one.
+    Plaintext,
+
+    /// The value looks like a ciphertext, but doesn't decrypt to text with
+    /// this key. Contains the reason.
+    Ambiguous(String),
+}
+
+/// One or more ciphers with one or more keys. Values are decrypted with the
+/// cipher and key that match them, and encrypted with the first cipher and
+/// the first key.
+pub struct Ciphers {
+    ciphers: Vec<Box<dyn Cipher>>,
+}
+
+impl Ciphers {
+    /// Returns the cipher with the given name, or all ciphers for "auto".
+    pub fn new(key: &str, name: &str) -> Result<Self, SimpleError> {
+        Self::with_keys(&[String::from(key)], name)
+    }
+
+    /// Returns the cipher with the given name, or all ciphers for "auto", for
+    /// every key. Values are decrypted with the first key that works, so the
+    /// newest key should come first.
+    pub fn with_keys(keys: &[String], name: &str) -> Result<Self, SimpleError> {
+        if keys.is_empty() {
+            return Err(SimpleError::new("Expected at least one encryption key"));
+        }
+
+        let names = match name {
+            AUTO_CIPHER => CIPHER_NAMES,
+            _ => CIPHER_NAMES.iter().find(|cipher_name| **cipher_name == name).map(std::slice::from_ref).ok_or_else(|| {
+                let message = format!("Unknown cipher '{name}', expected {AUTO_CIPHER} or one of {}", CIPHER_NAMES.join(", "));
+                SimpleError::new(message)
+            })?,
+        };
+
+        let ciphers = keys.iter().flat_map(|key| names.iter().map(move |cipher_name| -> Box<dyn Cipher> {
+            match *cipher_name {
+                "aes-ecb" => Box::new(AesEcb { key: key.clone() }),
+                "aes-cbc" => Box::new(AesCbc { key: key.clone() }),
+                _ => Box::new(Jasypt { password: key.clone() }),
+            }
+        })).collect();
+
+        Ok(Self { ciphers })
+    }
+
+    /// Encrypts the value with the first cipher.
+    pub fn encrypt(&self, value: &str) -> Result<String, SimpleError> {
+        self.ciphers[0].encrypt(value)
+    }
+
+    /// Encrypts the value with the cipher with the given name.
+    pub fn encrypt_with(&self, name: &str, value: &str) -> Result<String, SimpleError> {
+        match self.ciphers.iter().find(|cipher| cipher.name() == name) {
+            Some(cipher) => cipher.encrypt(value),
+            None => Err(SimpleError::new(format!("The cipher '{name}' is not selected"))),
+        }
+    }
+
+    /// Decrypts the value, or fails if it's not clearly encrypted with the key.
+    pub fn decrypt(&self, value: &str) -> Result<String, SimpleError> {
+        match self.classify(value) {
+            ValueKind::Encrypted(decrypted, _) => Ok(decrypted),
+            ValueKind::Plaintext => Err(SimpleError::new("The value is not encrypted")),
+            ValueKind::Ambiguous(reason) => Err(SimpleError::new(reason)),
+        }
+    }
+
+    /// Determines whether a value is encrypted with the key. Only values that
+    /// have the form of a ciphertext, decrypt with valid padding and contain
+    /// printable text are considered encrypted.
+    pub fn classify(&self, value: &str) -> ValueKind {
+        let mut reason = None;
+        for cipher in self.ciphers.iter().filter(|cipher| cipher.is_candidate(value)) {
+            match cipher.decrypt(value) {
+                Ok(decrypted) if decrypted.chars().any(|c| c.is_control() && c != '\t') => {
+                    reason.get_or_insert_with(|| String::from("it decrypts to text with control characters"));
+                },
+                Ok(decrypted) => return ValueKind::Encrypted(decrypted, cipher.name()),
+                Err(e) => {
+                    reason.get_or_insert_with(|| e.to_string());
+                },
+            }
+        }
+
+        match reason {
+            Some(reason) => ValueKind::Ambiguous(reason),
+            None => ValueKind::Plaintext,
+        }
+    }
+}
+
+/// Returns the number of bytes in a base64 encoded value.
+fn decoded_length(value: &str) -> Option<usize> {
+    general_purpose::STANDARD.decode(value).ok().map(|bytes| bytes.len())
+}
+
+/// Returns the base64 ciphertext in an `ENC(...)` wrapper.
+fn unwrap_jasypt(value: &str) -> Option<&str> {
+    value.trim().strip_prefix("ENC(")?.strip_suffix(')')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ciphers, ValueKind};
+
+    #[test]
+    fn test_if_values_are_classified() {
+        let ciphers = Ciphers::new("rNu7RWwJGnBN7GgI1TlyVvysAG6ELBeb", "auto").unwrap();
+        assert_eq!(ciphers.classify("6deMja63wuUxVeDncdUBog=="), ValueKind::Encrypted(String::from("testing"), "aes-ecb"));
+        assert_eq!(ciphers.classify("AAECAwQFBgcICQoLDA0OD+KfQHhQ04SOplGmBp6EU6g="), ValueKind::Encrypted(String::from("testing"), "aes-cbc"));
+        assert_eq!(ciphers.classify("ENC(AQIDBAUGBwgBIAwgSPX58A==)"), ValueKind::Encrypted(String::from("testing"), "jasypt"));
+        assert_eq!(ciphers.classify("7001"), ValueKind::Plaintext);
+        assert_eq!(ciphers.classify(""), ValueKind::Plaintext);
+        assert_eq!(ciphers.classify("https://db01:7002/MagicInfo"), ValueKind::Plaintext);
+        assert_eq!(ciphers.classify("dGVzdA=="), ValueKind::Plaintext);
+        assert!(matches!(ciphers.classify("AAAAAAAAAAAAAAAAAAAAAA=="), ValueKind::Ambiguous(_)));
+    }
+
+    #[test]
+    fn test_if_every_key_is_tried() {
+        let keys = [String::from("0123456789abcdefghijklmnopqrstuv"), String::from("rNu7RWwJGnBN7GgI1TlyVvysAG6ELBeb")];
+        let ciphers = Ciphers::with_keys(&keys, "aes-ecb").unwrap();
+        assert_eq!(ciphers.classify("6deMja63wuUxVeDncdUBog=="), ValueKind::Encrypted(String::from("testing"), "aes-ecb"));
+
+        let newest_ciphers = Ciphers::new(&keys[0], "aes-ecb").unwrap();
+        assert_eq!(newest_ciphers.decrypt(&ciphers.encrypt("testing").unwrap()).unwrap(), "testing");
+        assert!(Ciphers::with_keys(&[], "auto").is_err());
+    }
+
+    #[test]
+    fn test_if_only_the_selected_cipher_is_used() {
+        let ciphers = Ciphers::new("rNu7RWwJGnBN7GgI1TlyVvysAG6ELBeb", "jasypt").unwrap();
+        assert_eq!(ciphers.classify("6deMja63wuUxVeDncdUBog=="), ValueKind::Plaintext);
+        assert!(ciphers.encrypt("testing").unwrap().starts_with("ENC("));
+        assert!(Ciphers::new("key", "rot13").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::instance::get_instance;
 use crate::utils::print_error;
 
 use super::properties::PropertiesMut;
@@ -22,19 +23,36 @@ pub fn get_config_properties_path() -> Result<PathBuf, SimpleError> {
     Ok(config_properties_path)
 }
 
-/// Returns the MagicINFO home directory.
+/// Returns the MagicINFO home directory of the selected instance, or the one in
+/// MAGICINFO_PREMIUM_HOME.
 pub fn get_mi_home_dir() -> PathBuf {
-    let home_path_res = env::var("MAGICINFO_PREMIUM_HOME");
-    if let Err(_e) = home_path_res {
+    let home_path = find_mi_home_dir();
+    if home_path.is_none() {
         print_error("Could not find environment variable `MAGICINFO_PREMIUM_HOME`");
         exit(1);
     }
 
-    let home_path = home_path_res.unwrap();
-    let mut config_dir = PathBuf::new();
-    config_dir.push(home_path);
+    home_path.unwrap()
+}
+
+/// Returns the MagicINFO home directory, or None if there is none configured.
+pub fn find_mi_home_dir() -> Option<PathBuf> {
+    if let Some(home_path) = &get_instance().home {
+        return Some(home_path.clone());
+    }
+
+    env::var_os("MAGICINFO_PREMIUM_HOME").map(PathBuf::from)
+}
+
+/// Returns all properties of the config.properties file.
+pub fn get_config_values() -> Result<HashMap<String, String>, SimpleError> {
+    let config_properties_path = get_config_properties_path()?;
+    let config_properties_res = PropertiesMut::open(config_properties_path.to_str().unwrap());
+    if config_properties_res.is_err() {
+        return Err(SimpleError::new("Could not read the config.properties file."));
+    }
 
-    config_dir
+    config_properties_res.unwrap().get_hashmap_content()
 }
 
 /// Searches in the config.properties file for the requested properties.
@@ -51,11 +69,7 @@ pub fn get_config_properties(properties: &[&str]) -> Result<ConfigValueMap, Simp
     let mut config_map = ConfigValueMap::new();
 
     for property in properties {
-        let value = if let Some(val) = configuration.get(property.to_owned()) {
-            Some(val.clone())
-        } else {
-            None
-        };
+        let value = configuration.get(property.to_owned()).cloned();
 
         config_map.insert(String::from(property.to_owned()), value);
     }
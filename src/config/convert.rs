@@ -0,0 +1,277 @@
+use std::path::Path;
+use serde_json::{Map, Value};
+use simple_error::SimpleError;
+
+use super::UNSET_SENTINEL;
+
+/// The key used for the value of a key that also has nested keys, like
+/// `a.b` when `a.b.c` exists as well.
+const NESTED_VALUE_KEY: &str = "_";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Env,
+}
+
+impl Format {
+    /// Returns the format with the given name, as used on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "env" | "dotenv" => Some(Format::Env),
+            _ => None,
+        }
+    }
+
+    /// Returns the format that matches the extension of the file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+        if file_name == ".env" {
+            return Some(Format::Env);
+        }
+
+        Self::from_name(&path.extension()?.to_string_lossy().to_lowercase())
+    }
+}
+
+/// Converts properties to a document in the given format. If nested, every
+/// dotted key segment becomes a level in the document.
+pub fn export(properties: &[(String, String)], format: Format, nested: bool) -> Result<String, SimpleError> {
+    if format == Format::Env {
+        let lines: Vec<String> = properties.iter()
+            .map(|(key, value)| format!("{key}={}\n", quote_env_value(value)))
+            .collect();
+        return Ok(lines.concat());
+    }
+
+    let mut document = Map::new();
+    for (key, value) in properties {
+        if nested {
+            insert_nested(&mut document, key, value);
+        } else {
+            document.insert(key.clone(), Value::String(value.clone()));
+        }
+    }
+
+    let document = Value::Object(document);
+    let output_res = match format {
+        Format::Json => serde_json::to_string_pretty(&document).map(|json| json + "\n").map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::to_string(&document).map_err(|e| e.to_string()),
+        Format::Toml => toml::Value::try_from(&document)
+            .and_then(|value| toml::to_string(&value))
+            .map_err(|e| e.to_string()),
+        Format::Env => unreachable!(),
+    };
+
+    output_res.map_err(|e| {
+        let message = format!("Could not export the properties: {e}");
+        SimpleError::new(message)
+    })
+}
+
+/// Reads properties from a document in the given format. Nested keys are
+/// joined with dots. A key with a null value or the value `!unset` is
+/// returned without value, which means it should be removed.
+pub fn import(content: &str, format: Format) -> Result<Vec<(String, Option<String>)>, SimpleError> {
+    let document_res = match format {
+        Format::Json => serde_json::from_str::<Value>(content).map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::from_str::<Value>(content).map_err(|e| e.to_string()),
+        Format::Toml => toml::from_str::<Value>(content).map_err(|e| e.to_string()),
+        Format::Env => return parse_env(content),
+    };
+
+    let document = document_res.map_err(|e| {
+        let message = format!("Could not parse the document: {e}");
+        SimpleError::new(message)
+    })?;
+
+    let Value::Object(map) = document else {
+        return Err(SimpleError::new("Expected the document to contain an object of keys and values"));
+    };
+
+    let mut properties = Vec::new();
+    flatten(&map, "", &mut properties)?;
+    Ok(properties)
+}
+
+/// Inserts a value in the document at the path given by the dotted key.
+fn insert_nested(document: &mut Map<String, Value>, key: &str, value: &str) {
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let last_segment = segments.pop().unwrap();
+
+    let mut current = document;
+    for segment in segments {
+        let entry = current.entry(segment).or_insert_with(|| Value::Object(Map::new()));
+
+        // A key that was inserted as value earlier now needs nested keys, so
+        // its value moves to the nested value key.
+        if let Value::String(existing) = entry {
+            let mut nested = Map::new();
+            nested.insert(String::from(NESTED_VALUE_KEY), Value::String(existing.clone()));
+            *entry = Value::Object(nested);
+        }
+
+        let Value::Object(next) = entry else {
+            unreachable!();
+        };
+        current = next;
+    }
+
+    match current.get_mut(last_segment) {
+        Some(Value::Object(nested)) => {
+            nested.insert(String::from(NESTED_VALUE_KEY), Value::String(String::from(value)));
+        },
+        _ => {
+            current.insert(String::from(last_segment), Value::String(String::from(value)));
+        },
+    }
+}
+
+/// Converts a nested document to dotted keys and values.
+fn flatten(map: &Map<String, Value>, prefix: &str, properties: &mut Vec<(String, Option<String>)>) -> Result<(), SimpleError> {
+    for (segment, value) in map {
+        let key = match (prefix.is_empty(), segment.as_str()) {
+            (false, NESTED_VALUE_KEY) => String::from(prefix),
+            (true, _) => segment.clone(),
+            (false, _) => format!("{prefix}.{segment}"),
+        };
+
+        match value {
+            Value::Object(nested) => flatten(nested, &key, properties)?,
+            Value::Null => properties.push((key, None)),
+            Value::String(text) if text == UNSET_SENTINEL => properties.push((key, None)),
+            Value::String(text) => properties.push((key, Some(text.clone()))),
+            Value::Bool(_) | Value::Number(_) => properties.push((key, Some(value.to_string()))),
+            Value::Array(_) => {
+                let message = format!("The value of '{key}' is a list, which can't be stored in a properties file");
+                return Err(SimpleError::new(message));
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Quotes a value for a dotenv file, if needed.
+fn quote_env_value(value: &str) -> String {
+    let is_plain = value.chars().all(|c| c.is_ascii_alphanumeric() || "_-.:/@,+%".contains(c));
+    if is_plain && !value.is_empty() {
+        return String::from(value);
+    }
+
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{escaped}\"")
+}
+
+/// Parses a dotenv file with `KEY=value` lines. Values can be unquoted, single
+/// quoted (literal) or double quoted (with backslash escapes).
+fn parse_env(content: &str) -> Result<Vec<(String, Option<String>)>, SimpleError> {
+    let mut properties = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, raw_value)) = line.split_once('=') else {
+            let message = format!("Line {} is not a KEY=value pair", index + 1);
+            return Err(SimpleError::new(message));
+        };
+
+        let raw_value = raw_value.trim();
+        let value = if let Some(quoted) = raw_value.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+            unescape_env_value(quoted)
+        } else if let Some(quoted) = raw_value.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')) {
+            String::from(quoted)
+        } else {
+            // Unquoted values can end with a comment.
+            String::from(raw_value.split(" #").next().unwrap().trim_end())
+        };
+
+        let value = if value == UNSET_SENTINEL { None } else { Some(value) };
+        properties.push((String::from(key.trim()), value));
+    }
+
+    Ok(properties)
+}
+
+fn unescape_env_value(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some('t') => output.push('\t'),
+            Some(other) => output.push(other),
+            None => output.push('\\'),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, Format};
+
+    fn properties() -> Vec<(String, String)> {
+        vec![
+            (String::from("device.log4j"), String::from("on")),
+            (String::from("device.log4j.level"), String::from("INFO")),
+            (String::from("wsrm.url"), String::from("jdbc:postgresql://localhost:5432/magicinfo")),
+            (String::from("web_url"), String::from("say \"hi\" $HOME")),
+        ]
+    }
+
+    fn round_trip(format: Format, nested: bool) {
+        let exported = export(&properties(), format, nested).unwrap();
+        let mut imported: Vec<(String, String)> = import(&exported, format).unwrap()
+            .into_iter()
+            .map(|(key, value)| (key, value.unwrap()))
+            .collect();
+        imported.sort();
+
+        let mut expected = properties();
+        expected.sort();
+        assert_eq!(imported, expected, "{exported}");
+    }
+
+    #[test]
+    fn test_if_all_formats_round_trip() {
+        for format in [Format::Json, Format::Yaml, Format::Toml, Format::Env] {
+            round_trip(format, false);
+        }
+        for format in [Format::Json, Format::Yaml, Format::Toml] {
+            round_trip(format, true);
+        }
+    }
+
+    #[test]
+    fn test_if_nested_documents_are_flattened() {
+        let imported = import("{\"wsrm\": {\"url\": \"jdbc\", \"port\": 5432, \"old\": null}}", Format::Json).unwrap();
+        assert_eq!(imported, vec![
+            (String::from("wsrm.old"), None),
+            (String::from("wsrm.port"), Some(String::from("5432"))),
+            (String::from("wsrm.url"), Some(String::from("jdbc"))),
+        ]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::syntax::format_property;
+
+/// The key-level differences between two properties files. Keys are kept in
+/// alphabetical order.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PropertiesDiff {
+    pub added: BTreeMap<String, String>,
+    pub removed: BTreeMap<String, String>,
+    pub changed: BTreeMap<String, ChangedValue>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChangedValue {
+    pub old: String,
+    pub new: String,
+}
+
+/// Compares two sets of properties key by key. Ordering, comments and
+/// formatting of the files don't matter.
+pub fn diff(old: &HashMap<String, String>, new: &HashMap<String, String>) -> PropertiesDiff {
+    let mut properties_diff = PropertiesDiff::default();
+
+    for (key, old_value) in old {
+        match new.get(key) {
+            None => {
+                properties_diff.removed.insert(key.clone(), old_value.clone());
+            },
+            Some(new_value) if new_value != old_value => {
+                properties_diff.changed.insert(key.clone(), ChangedValue {
+                    old: old_value.clone(),
+                    new: new_value.clone(),
+                });
+            },
+            _ => {},
+        }
+    }
+
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            properties_diff.added.insert(key.clone(), new_value.clone());
+        }
+    }
+
+    properties_diff
+}
+
+impl PropertiesDiff {
+    /// Replaces the values with the text returned for them, like a mask for
+    /// secret values.
+    pub fn redact(&mut self, redact: fn(&str, &str) -> String) {
+        for (key, value) in self.added.iter_mut().chain(self.removed.iter_mut()) {
+            *value = redact(key, value);
+        }
+        for (key, change) in self.changed.iter_mut() {
+            change.old = redact(key, &change.old);
+            change.new = redact(key, &change.new);
+        }
+    }
+}
+
+/// Formats the differences as one line per key, marked with '+' for added,
+/// '-' for removed and '~' for changed keys.
+pub fn format_text(properties_diff: &PropertiesDiff) -> String {
+    let keys: BTreeSet<&String> = properties_diff.added.keys()
+        .chain(properties_diff.removed.keys())
+        .chain(properties_diff.changed.keys())
+        .collect();
+
+    let mut output = String::new();
+    for key in keys {
+        if let Some(value) = properties_diff.added.get(key) {
+            output.push_str(&format!("+ {key} = {value}\n"));
+        } else if let Some(value) = properties_diff.removed.get(key) {
+            output.push_str(&format!("- {key} = {value}\n"));
+        } else if let Some(change) = properties_diff.changed.get(key) {
+            output.push_str(&format!("~ {key} = {} -> {}\n", change.old, change.new));
+        }
+    }
+
+    output
+}
+
+/// Formats the differences as a unified diff of both files, normalized to
+/// alphabetically sorted `key=value` lines, without context lines. The values
+/// are compared as they are, but printed as returned by redact.
+pub fn format_unified(old_name: &str, old: &HashMap<String, String>, new_name: &str, new: &HashMap<String, String>, redact: fn(&str, &str) -> String) -> String {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut output = format!("--- {old_name}\n+++ {new_name}\n");
+
+    // Line numbers of the next line in the normalized old and new files.
+    let mut old_line = 1;
+    let mut new_line = 1;
+    let mut hunk: Option<Hunk> = None;
+
+    for key in keys {
+        let old_value = old.get(key);
+        let new_value = new.get(key);
+
+        if old_value == new_value {
+            if let Some(finished) = hunk.take() {
+                output.push_str(&finished.format());
+            }
+            old_line += 1;
+            new_line += 1;
+            continue;
+        }
+
+        let current = hunk.get_or_insert_with(|| Hunk::new(old_line, new_line));
+        if let Some(value) = old_value {
+            current.removed.push(format_property(key, "=", &redact(key, value)));
+            old_line += 1;
+        }
+        if let Some(value) = new_value {
+            current.added.push(format_property(key, "=", &redact(key, value)));
+            new_line += 1;
+        }
+    }
+
+    if let Some(finished) = hunk.take() {
+        output.push_str(&finished.format());
+    }
+
+    output
+}
+
+/// A group of consecutive changed lines in a unified diff.
+struct Hunk {
+    old_start: usize,
+    new_start: usize,
+    removed: Vec<String>,
+    added: Vec<String>,
+}
+
+impl Hunk {
+    fn new(old_start: usize, new_start: usize) -> Self {
+        Self {
+            old_start,
+            new_start,
+            removed: Vec::new(),
+            added: Vec::new(),
+        }
+    }
+
+    fn format(&self) -> String {
+        // An empty side of a hunk refers to the line before it.
+        let range = |start: usize, length: usize| {
+            let start = if length == 0 { start - 1 } else { start };
+            format!("{start},{length}")
+        };
+
+        let mut output = format!("@@ -{} +{} @@\n", range(self.old_start, self.removed.len()), range(self.new_start, self.added.len()));
+        for line in &self.removed {
+            output.push_str(&format!("-{line}\n"));
+        }
+        for line in &self.added {
+            output.push_str(&format!("+{line}\n"));
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{diff, format_unified};
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (String::from(*key), String::from(*value))).collect()
+    }
+
+    #[test]
+    fn test_if_unified_diff_is_generated() {
+        let old = map(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4")]);
+        let new = map(&[("a", "1"), ("b", "20"), ("d", "4"), ("e", "5")]);
+
+        let properties_diff = diff(&old, &new);
+        assert_eq!(properties_diff.added.len(), 1);
+        assert_eq!(properties_diff.removed.len(), 1);
+        assert_eq!(properties_diff.changed.len(), 1);
+
+        let unified = format_unified("old", &old, "new", &new, |_, value| String::from(value));
+        assert_eq!(unified, "--- old\n+++ new\n@@ -2,2 +2,1 @@\n-b=2\n-c=3\n+b=20\n@@ -4,0 +4,1 @@\n+e=5\n");
+    }
+}
@@ -2,6 +2,7 @@ use aes::Aes128;
 use aes::cipher::{BlockEncrypt, BlockDecrypt, KeyInit,
     generic_array::GenericArray,
 };
+use des::Des;
 use simple_error::SimpleError;
 use base64::{Engine as _, engine::general_purpose};
 
@@ -12,7 +13,7 @@ pub fn aes_128_ecb_encrypt(key: &str, value: &str) -> Result<String, simple_erro
     }
 
     let part_of_key = &key.as_bytes()[..size];
-    let cipher = Aes128::new(&GenericArray::from_slice(part_of_key));
+    let cipher = Aes128::new(GenericArray::from_slice(part_of_key));
     let plaintext_bytes = pkcs5_padding(value.as_bytes(), size);
     let mut encrypted: Vec<u8> = vec![0; plaintext_bytes.len()];
 
@@ -47,11 +48,15 @@ pub fn aes_128_ecb_decrypt(key: &str, encrypted_string: &str) -> Result<String,
 
     let encrypted = general_purpose::STANDARD.decode(encrypted_string).map_err(|e| {
         let error_message = format!("Could not base64 decode encrypted string: {e}");
-        return SimpleError::new(error_message)
+        SimpleError::new(error_message)
     })?;
 
+    if encrypted.is_empty() || encrypted.len() % size != 0 {
+        return Err(SimpleError::new("The encrypted value is not a multiple of the AES block size"));
+    }
+
     let part_of_key = &key.as_bytes()[..size];
-    let cipher = Aes128::new(&GenericArray::from_slice(part_of_key));
+    let cipher = Aes128::new(GenericArray::from_slice(part_of_key));
     let mut decrypted: Vec<u8> = vec![0; encrypted.len()];
 
     let mut block_start = 0;
@@ -73,15 +78,39 @@ pub fn aes_128_ecb_decrypt(key: &str, encrypted_string: &str) -> Result<String,
         }
     }
 
-    let decrypted_bytes = pkcs5_unpadding(&decrypted[..]);
+    let decrypted_bytes = pkcs5_unpadding(&decrypted[..], size)?;
     let decrypted_string = String::from_utf8(decrypted_bytes).map_err(|e| {
         let error_message = format!("Could not parse decrypted value into a string: {e}");
-        return SimpleError::new(error_message)
+        SimpleError::new(error_message)
     })?;
 
     Ok(decrypted_string)
 }
 
+/// Generates a random key of 32 letters and digits, like the keys MagicINFO
+/// generates itself.
+pub fn generate_key() -> Result<String, SimpleError> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let mut key = String::with_capacity(32);
+
+    while key.len() < 32 {
+        let mut bytes = [0u8; 64];
+        getrandom::getrandom(&mut bytes).map_err(|e| {
+            let error_message = format!("Could not generate a random key: {e}");
+            SimpleError::new(error_message)
+        })?;
+
+        // Bytes above the largest multiple of the alphabet length are skipped,
+        // so every character is equally likely.
+        let limit = 256 - (256 % ALPHABET.len());
+        for byte in bytes.iter().filter(|byte| (**byte as usize) < limit).take(32 - key.len()) {
+            key.push(ALPHABET[*byte as usize % ALPHABET.len()] as char);
+        }
+    }
+
+    Ok(key)
+}
+
 fn pkcs5_padding(data: &[u8], block_size: usize) -> Vec<u8> {
     let padding = block_size - (data.len() % block_size);
     let mut data_vec = Vec::from(data);
@@ -91,14 +120,165 @@ fn pkcs5_padding(data: &[u8], block_size: usize) -> Vec<u8> {
     data_vec
 }
 
-fn pkcs5_unpadding(data: &[u8]) -> Vec<u8> {
+fn pkcs5_unpadding(data: &[u8], block_size: usize) -> Result<Vec<u8>, SimpleError> {
     let length = data.len();
-    let padding = data.last().unwrap().clone() as usize;
+    let Some(&last_byte) = data.last() else {
+        return Err(SimpleError::new("The decrypted value is empty"));
+    };
 
-    let data_vec = Vec::from(&data[..(length-padding)]);
-    data_vec
+    let padding = last_byte as usize;
+    if padding == 0 || padding > block_size || padding > length {
+        return Err(SimpleError::new("The decrypted value has invalid padding"));
+    }
+
+    // Every padding byte has the value of the padding length.
+    if data[length - padding..].iter().any(|byte| *byte != last_byte) {
+        return Err(SimpleError::new("The decrypted value has invalid padding"));
+    }
+
+    Ok(Vec::from(&data[..(length-padding)]))
+}
+
+/// Encrypts the value with AES-128 in CBC mode, using the first 16 bytes of
+/// the key and a random IV. Returns the IV followed by the ciphertext, base64
+/// encoded.
+pub fn aes_128_cbc_encrypt(key: &str, value: &str) -> Result<String, SimpleError> {
+    let size = 16;
+    if key.len() < size {
+        return Err(SimpleError::new("Expected an encryption key of at least 16 bytes"))
+    }
+
+    let cipher = Aes128::new(GenericArray::from_slice(&key.as_bytes()[..size]));
+    let mut iv = [0u8; 16];
+    getrandom::getrandom(&mut iv).map_err(|e| {
+        let error_message = format!("Could not generate a random IV: {e}");
+        SimpleError::new(error_message)
+    })?;
+
+    let mut encrypted = Vec::from(iv);
+    encrypted.extend(cbc_encrypt(&pkcs5_padding(value.as_bytes(), size), &iv, |block| {
+        let mut block = GenericArray::clone_from_slice(block);
+        cipher.encrypt_block(&mut block);
+        block.to_vec()
+    }));
+
+    Ok(general_purpose::STANDARD.encode(encrypted))
+}
+
+/// Decrypts a value encrypted with `aes_128_cbc_encrypt`.
+pub fn aes_128_cbc_decrypt(key: &str, encrypted_string: &str) -> Result<String, SimpleError> {
+    let size = 16;
+    if key.len() < size {
+        return Err(SimpleError::new("Expected an encryption key of at least 16 bytes"))
+    }
+
+    let encrypted = base64_decode(encrypted_string)?;
+    if encrypted.len() < 2 * size || encrypted.len() % size != 0 {
+        return Err(SimpleError::new("The encrypted value is not an IV followed by whole AES blocks"));
+    }
+
+    let cipher = Aes128::new(GenericArray::from_slice(&key.as_bytes()[..size]));
+    let (iv, ciphertext) = encrypted.split_at(size);
+    let decrypted = cbc_decrypt(ciphertext, iv, |block| {
+        let mut block = GenericArray::clone_from_slice(block);
+        cipher.decrypt_block(&mut block);
+        block.to_vec()
+    });
+
+    bytes_to_string(pkcs5_unpadding(&decrypted, size)?)
+}
+
+/// Encrypts the value like Jasypt's default PBEWithMD5AndDES encryptor, with
+/// a random salt. Returns the salt followed by the ciphertext, base64 encoded.
+pub fn pbe_md5_des_encrypt(password: &str, value: &str) -> Result<String, SimpleError> {
+    let mut salt = [0u8; 8];
+    getrandom::getrandom(&mut salt).map_err(|e| {
+        let error_message = format!("Could not generate a random salt: {e}");
+        SimpleError::new(error_message)
+    })?;
+
+    let (cipher, iv) = derive_des_cipher(password, &salt);
+    let mut encrypted = Vec::from(salt);
+    encrypted.extend(cbc_encrypt(&pkcs5_padding(value.as_bytes(), 8), &iv, |block| {
+        let mut block = GenericArray::clone_from_slice(block);
+        cipher.encrypt_block(&mut block);
+        block.to_vec()
+    }));
+
+    Ok(general_purpose::STANDARD.encode(encrypted))
+}
+
+/// Decrypts a value encrypted with `pbe_md5_des_encrypt`.
+pub fn pbe_md5_des_decrypt(password: &str, encrypted_string: &str) -> Result<String, SimpleError> {
+    let encrypted = base64_decode(encrypted_string)?;
+    if encrypted.len() < 16 || encrypted.len() % 8 != 0 {
+        return Err(SimpleError::new("The encrypted value is not a salt followed by whole DES blocks"));
+    }
+
+    let (salt, ciphertext) = encrypted.split_at(8);
+    let (cipher, iv) = derive_des_cipher(password, salt);
+    let decrypted = cbc_decrypt(ciphertext, &iv, |block| {
+        let mut block = GenericArray::clone_from_slice(block);
+        cipher.decrypt_block(&mut block);
+        block.to_vec()
+    });
+
+    bytes_to_string(pkcs5_unpadding(&decrypted, 8)?)
+}
+
+/// Derives the DES key and IV from the password and salt with PBKDF1, using
+/// MD5 and 1000 iterations like Jasypt does.
+fn derive_des_cipher(password: &str, salt: &[u8]) -> (Des, [u8; 8]) {
+    let mut digest = md5::compute([password.as_bytes(), salt].concat()).0;
+    for _ in 1..1000 {
+        digest = md5::compute(digest).0;
+    }
+
+    let cipher = Des::new(GenericArray::from_slice(&digest[..8]));
+    let mut iv = [0u8; 8];
+    iv.copy_from_slice(&digest[8..]);
+    (cipher, iv)
+}
+
+/// Chains the encryption of padded data in CBC mode.
+fn cbc_encrypt(data: &[u8], iv: &[u8], encrypt_block: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let mut previous = Vec::from(iv);
+    let mut encrypted = Vec::with_capacity(data.len());
+    for block in data.chunks(iv.len()) {
+        let mixed: Vec<u8> = block.iter().zip(&previous).map(|(byte, previous_byte)| byte ^ previous_byte).collect();
+        previous = encrypt_block(&mixed);
+        encrypted.extend(&previous);
+    }
+
+    encrypted
 }
 
+/// Chains the decryption of data in CBC mode. The data has to consist of whole
+/// blocks.
+fn cbc_decrypt(data: &[u8], iv: &[u8], decrypt_block: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let mut previous = iv;
+    let mut decrypted = Vec::with_capacity(data.len());
+    for block in data.chunks(iv.len()) {
+        decrypted.extend(decrypt_block(block).iter().zip(previous).map(|(byte, previous_byte)| byte ^ previous_byte));
+        previous = block;
+    }
+
+    decrypted
+}
+
+fn base64_decode(encrypted_string: &str) -> Result<Vec<u8>, SimpleError> {
+    general_purpose::STANDARD.decode(encrypted_string).map_err(|e| {
+        let error_message = format!("Could not base64 decode encrypted string: {e}");
+        SimpleError::new(error_message)
+    })
+}
+
+fn bytes_to_string(bytes: Vec<u8>) -> Result<String, SimpleError> {
+    String::from_utf8(bytes).map_err(|e| {
+        let error_message = format!("Could not parse decrypted value into a string: {e}");
+        SimpleError::new(error_message)
+    })
+}
 
 #[cfg(test)]
 mod tests {
@@ -111,11 +291,48 @@ mod tests {
         let encrypted_str = "6deMja63wuUxVeDncdUBog==";
 
         let decrypted = aes_128_ecb_decrypt(key, encrypted_str);
-        assert_eq!(decrypted.is_ok(), true);
+        assert!(decrypted.is_ok());
         let decrypted_value = decrypted.unwrap();
         assert_eq!(decrypted_value, "testing");
     }
 
+    #[test]
+    fn test_if_other_schemes_decrypt() {
+        use super::{aes_128_cbc_decrypt, aes_128_cbc_encrypt, pbe_md5_des_decrypt, pbe_md5_des_encrypt};
+
+        let key = "rNu7RWwJGnBN7GgI1TlyVvysAG6ELBeb";
+        assert_eq!(aes_128_cbc_decrypt(key, "AAECAwQFBgcICQoLDA0OD+KfQHhQ04SOplGmBp6EU6g=").unwrap(), "testing");
+        assert_eq!(pbe_md5_des_decrypt(key, "AQIDBAUGBwgBIAwgSPX58A==").unwrap(), "testing");
+
+        let value = "a value longer than a single block";
+        assert_eq!(aes_128_cbc_decrypt(key, &aes_128_cbc_encrypt(key, value).unwrap()).unwrap(), value);
+        assert_eq!(pbe_md5_des_decrypt(key, &pbe_md5_des_encrypt(key, value).unwrap()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_if_invalid_padding_is_rejected() {
+        use super::pkcs5_unpadding;
+
+        assert_eq!(pkcs5_unpadding(&[b'a', b'b', 2, 2], 4).unwrap(), vec![b'a', b'b']);
+        assert!(pkcs5_unpadding(&[b'a', b'b', 1, 2], 4).is_err());
+        assert!(pkcs5_unpadding(&[b'a', b'b', b'c', 0], 4).is_err());
+        assert!(pkcs5_unpadding(&[b'a', b'b', b'c', 5], 4).is_err());
+        assert!(pkcs5_unpadding(&[], 4).is_err());
+    }
+
+    #[test]
+    fn test_if_generated_keys_can_be_used() {
+        use super::{aes_128_ecb_decrypt, aes_128_ecb_encrypt, generate_key};
+
+        let key = generate_key().unwrap();
+        assert_eq!(key.len(), 32);
+        assert!(key.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(key, generate_key().unwrap());
+
+        let encrypted = aes_128_ecb_encrypt(&key, "testing").unwrap();
+        assert_eq!(aes_128_ecb_decrypt(&key, &encrypted).unwrap(), "testing");
+    }
+
     #[test]
     fn test_if_encryption_works() {
         use super::aes_128_ecb_encrypt;
@@ -124,7 +341,7 @@ mod tests {
         let plain_str = "testing";
 
         let encrypted = aes_128_ecb_encrypt(key, plain_str);
-        assert_eq!(encrypted.is_ok(), true);
+        assert!(encrypted.is_ok());
         let encrypted_value = encrypted.unwrap();
         assert_eq!(encrypted_value, "6deMja63wuUxVeDncdUBog==");
     }
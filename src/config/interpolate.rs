@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use simple_error::SimpleError;
+
+use crate::utils::print_error;
+
+/// Replaces `${...}` references in property values. A reference resolves to
+/// the environment variable with that name, or else to the property with that
+/// key. Supported forms:
+///
+/// - `${NAME}` is replaced with the value of NAME
+/// - `${NAME:-default}` uses the default if NAME is unset or empty
+/// - `${NAME:?message}` fails with the message if NAME is unset or empty
+/// - `$${` is written as a literal `${`
+pub struct Interpolator<'a> {
+    /// Properties that are already in the file. Their values are used as is.
+    properties: &'a HashMap<String, String>,
+
+    /// Properties that are about to be written. Their values can contain
+    /// references themselves, which are resolved when they are referenced.
+    pending: &'a HashMap<String, String>,
+
+    /// Leaves unresolved references in the value instead of failing.
+    allow_unresolved: bool,
+}
+
+impl<'a> Interpolator<'a> {
+    pub fn new(properties: &'a HashMap<String, String>, pending: &'a HashMap<String, String>, allow_unresolved: bool) -> Self {
+        Self {
+            properties,
+            pending,
+            allow_unresolved,
+        }
+    }
+
+    /// Returns the value for the given key with all references resolved.
+    pub fn interpolate(&self, key: &str, value: &str) -> Result<String, SimpleError> {
+        let mut stack = vec![String::from(key)];
+        self.resolve(value, &mut stack).map_err(|e| {
+            let message = format!("Could not interpolate the value of '{key}': {e}");
+            SimpleError::new(message)
+        })
+    }
+
+    /// Resolves all references in the text. The stack contains the keys that
+    /// are being resolved, to detect circular references.
+    fn resolve(&self, text: &str, stack: &mut Vec<String>) -> Result<String, SimpleError> {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('$') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(escaped) = rest.strip_prefix("$${") {
+                output.push_str("${");
+                rest = escaped;
+                continue;
+            }
+
+            let Some(reference_start) = rest.strip_prefix("${") else {
+                output.push('$');
+                rest = &rest[1..];
+                continue;
+            };
+
+            let Some(end) = find_closing_brace(reference_start) else {
+                return Err(SimpleError::new(format!("Missing '}}' in '{rest}'")));
+            };
+
+            let reference = &reference_start[..end];
+            output.push_str(&self.resolve_reference(reference, stack)?);
+            rest = &reference_start[end + 1..];
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    /// Resolves the content of a single `${...}` reference.
+    fn resolve_reference(&self, reference: &str, stack: &mut Vec<String>) -> Result<String, SimpleError> {
+        let (name, modifier) = match reference.find(":-").or_else(|| reference.find(":?")) {
+            Some(index) => (&reference[..index], Some((&reference[index..index + 2], &reference[index + 2..]))),
+            None => (reference, None),
+        };
+
+        if name.is_empty() {
+            return Err(SimpleError::new(format!("Empty reference '${{{reference}}}'")));
+        }
+
+        let value = self.lookup(name, stack)?.filter(|value| modifier.is_none() || !value.is_empty());
+        match (value, modifier) {
+            (Some(value), _) => Ok(value),
+            (None, Some((":-", default))) => self.resolve(default, stack),
+            (None, Some((_, message))) => {
+                let message = if message.is_empty() { format!("'{name}' is not set") } else { self.resolve(message, stack)? };
+                Err(SimpleError::new(message))
+            },
+            (None, None) if self.allow_unresolved => {
+                let error_message = format!("'{name}' could not be resolved, the reference is left as is.");
+                print_error(error_message);
+                Ok(format!("${{{reference}}}"))
+            },
+            (None, None) => Err(SimpleError::new(format!("'{name}' is neither an environment variable nor a property"))),
+        }
+    }
+
+    /// Looks up a name as environment variable, pending property or existing
+    /// property, in that order.
+    fn lookup(&self, name: &str, stack: &mut Vec<String>) -> Result<Option<String>, SimpleError> {
+        if let Ok(value) = std::env::var(name) {
+            return Ok(Some(value));
+        }
+
+        if let Some(value) = self.pending.get(name) {
+            if stack.iter().any(|key| key == name) {
+                let chain = stack.join(" -> ");
+                return Err(SimpleError::new(format!("Circular reference {chain} -> {name}")));
+            }
+
+            stack.push(String::from(name));
+            let resolved = self.resolve(value, stack);
+            stack.pop();
+            return resolved.map(Some);
+        }
+
+        Ok(self.properties.get(name).cloned())
+    }
+}
+
+/// Returns the index of the '}' that closes a reference, skipping the braces
+/// of nested references.
+fn find_closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let bytes = text.as_bytes();
+
+    for (index, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'{' if index > 0 && bytes[index - 1] == b'$' => depth += 1,
+            b'}' if depth == 0 => return Some(index),
+            b'}' => depth -= 1,
+            _ => {},
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::Interpolator;
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (String::from(*key), String::from(*value))).collect()
+    }
+
+    #[test]
+    fn test_if_references_are_resolved() {
+        std::env::set_var("MAGICUTIL_TEST_DB_HOST", "db01");
+        let properties = map(&[("db.port", "5432"), ("log.path", "${catalina.home}/logs")]);
+        let pending = map(&[("db.name", "${MAGICUTIL_TEST_DB_NAME:-magicinfo}")]);
+        let interpolator = Interpolator::new(&properties, &pending, false);
+
+        let value = interpolator.interpolate("wsrm.url", "jdbc:postgresql://${MAGICUTIL_TEST_DB_HOST}:${db.port}/${db.name}");
+        assert_eq!(value.unwrap(), "jdbc:postgresql://db01:5432/magicinfo");
+
+        let value = interpolator.interpolate("copy", "${log.path} costs $5 and $${literal}");
+        assert_eq!(value.unwrap(), "${catalina.home}/logs costs $5 and ${literal}");
+    }
+
+    #[test]
+    fn test_if_unresolved_references_fail() {
+        let properties = HashMap::new();
+        let pending = map(&[("a", "${b}"), ("b", "${a}")]);
+        let interpolator = Interpolator::new(&properties, &pending, false);
+
+        assert!(interpolator.interpolate("key", "${MAGICUTIL_TEST_UNSET}").is_err());
+        assert!(interpolator.interpolate("a", "${b}").is_err());
+
+        let error = interpolator.interpolate("key", "${MAGICUTIL_TEST_UNSET:?database host required}").unwrap_err();
+        assert!(error.to_string().ends_with("database host required"));
+
+        let interpolator = Interpolator::new(&properties, &pending, true);
+        assert_eq!(interpolator.interpolate("key", "x${MAGICUTIL_TEST_UNSET}").unwrap(), "x${MAGICUTIL_TEST_UNSET}");
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use simple_error::SimpleError;
+
+/// The value of wsrm.dbVendor for a PostgreSQL database.
+pub const POSTGRESQL_VENDOR: &str = "PostgreSQL";
+
+/// The value of wsrm.dbVendor for a SQL Server database.
+pub const SQLSERVER_VENDOR: &str = "MSSQL";
+
+/// The parts of a PostgreSQL or SQL Server JDBC URL.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JdbcUrl {
+    /// The vendor as MagicINFO names it in wsrm.dbVendor.
+    pub vendor: &'static str,
+    pub host: String,
+
+    /// The named instance of a SQL Server, like SQLEXPRESS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+
+    /// The port, or the default port of the vendor if the URL has none.
+    pub port: u16,
+    pub database: Option<String>,
+    pub parameters: BTreeMap<String, String>,
+}
+
+/// Parses a PostgreSQL or SQL Server JDBC URL, like
+/// `jdbc:postgresql://host:5432/magicinfo?ssl=true` or
+/// `jdbc:sqlserver://host\SQLEXPRESS:1433;databaseName=magicinfo`.
+pub fn parse(url: &str) -> Result<JdbcUrl, SimpleError> {
+    let url = url.trim();
+    if let Some(rest) = url.strip_prefix("jdbc:postgresql:") {
+        return parse_postgresql(rest).ok_or_else(|| SimpleError::new("Expected jdbc:postgresql://host[:port]/database[?parameters]"));
+    }
+    if let Some(rest) = url.strip_prefix("jdbc:sqlserver:") {
+        return parse_sqlserver(rest).ok_or_else(|| SimpleError::new("Expected jdbc:sqlserver://host[\\instance][:port][;property=value]"));
+    }
+
+    Err(SimpleError::new("Expected a jdbc:postgresql: or jdbc:sqlserver: url"))
+}
+
+/// Parses the part after `jdbc:postgresql:`. Without `//`, the database is on
+/// localhost.
+fn parse_postgresql(rest: &str) -> Option<JdbcUrl> {
+    let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let parameters = query.split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .map(|parameter| {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            (String::from(key), String::from(value))
+        })
+        .collect();
+
+    let Some(authority_and_path) = location.strip_prefix("//") else {
+        let database = location.trim_start_matches('/');
+        return Some(JdbcUrl {
+            vendor: POSTGRESQL_VENDOR,
+            host: String::from("localhost"),
+            instance: None,
+            port: 5432,
+            database: Some(String::from(database)).filter(|database| !database.is_empty()),
+            parameters,
+        });
+    };
+
+    let (authority, database) = authority_and_path.split_once('/').unwrap_or((authority_and_path, ""));
+
+    // Only the first of multiple hosts is used.
+    let authority = authority.split(',').next().unwrap();
+    let (host, port) = split_host_port(authority)?;
+
+    Some(JdbcUrl {
+        vendor: POSTGRESQL_VENDOR,
+        host: if host.is_empty() { String::from("localhost") } else { host },
+        instance: None,
+        port: port.unwrap_or(5432),
+        database: Some(String::from(database)).filter(|database| !database.is_empty()),
+        parameters,
+    })
+}
+
+/// Parses the part after `jdbc:sqlserver:`. The server, instance, port and
+/// database can also be given as properties.
+fn parse_sqlserver(rest: &str) -> Option<JdbcUrl> {
+    let rest = rest.strip_prefix("//")?;
+    let mut parts = rest.split(';');
+    let server = parts.next().unwrap();
+
+    let parameters: BTreeMap<String, String> = parts
+        .filter(|parameter| !parameter.trim().is_empty())
+        .map(|parameter| {
+            let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            (String::from(key.trim()), String::from(value.trim()))
+        })
+        .collect();
+
+    // Property names are case-insensitive.
+    let property = |name: &str| parameters.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+        .filter(|value| !value.is_empty());
+
+    let (server, port) = split_host_port(server)?;
+    let (host, instance) = match server.split_once('\\') {
+        Some((host, instance)) => (String::from(host), Some(String::from(instance))),
+        None => (server, None),
+    };
+
+    let host = Some(host).filter(|host| !host.is_empty()).or_else(|| property("serverName")).unwrap_or_else(|| String::from("localhost"));
+    let port = match (port, property("portNumber").or_else(|| property("port"))) {
+        (Some(port), _) => port,
+        (None, Some(port)) => port.parse().ok()?,
+        (None, None) => 1433,
+    };
+
+    Some(JdbcUrl {
+        vendor: SQLSERVER_VENDOR,
+        host,
+        instance: instance.or_else(|| property("instanceName")),
+        port,
+        database: property("databaseName").or_else(|| property("database")),
+        parameters,
+    })
+}
+
+/// Splits `host:port`, where the host can be an IPv6 address in brackets.
+/// Returns None if the port is not a valid port number.
+fn split_host_port(authority: &str) -> Option<(String, Option<u16>)> {
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, rest) = bracketed.split_once(']')?;
+            (host, rest.strip_prefix(':'))
+        },
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+
+    let port = match port {
+        Some(port) => Some(port.parse::<u16>().ok().filter(|port| *port > 0)?),
+        None => None,
+    };
+
+    Some((String::from(host), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn test_if_postgresql_urls_are_parsed() {
+        let url = parse("jdbc:postgresql://db01:5433/magicinfo?ssl=true&sslmode=require").unwrap();
+        assert_eq!((url.vendor, url.host.as_str(), url.port, url.database.as_deref()), ("PostgreSQL", "db01", 5433, Some("magicinfo")));
+        assert_eq!(url.parameters.get("sslmode").map(|value| value.as_str()), Some("require"));
+
+        let url = parse("jdbc:postgresql://[::1]/magicinfo").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 5432));
+
+        let url = parse("jdbc:postgresql:magicinfo").unwrap();
+        assert_eq!((url.host.as_str(), url.database.as_deref()), ("localhost", Some("magicinfo")));
+
+        assert!(parse("jdbc:postgresql://db01:port/magicinfo").is_err());
+    }
+
+    #[test]
+    fn test_if_sqlserver_urls_are_parsed() {
+        let url = parse("jdbc:sqlserver://db01\\SQLEXPRESS:1434;databaseName=magicinfo;encrypt=true").unwrap();
+        assert_eq!((url.vendor, url.host.as_str(), url.instance.as_deref(), url.port), ("MSSQL", "db01", Some("SQLEXPRESS"), 1434));
+        assert_eq!(url.database.as_deref(), Some("magicinfo"));
+
+        let url = parse("jdbc:sqlserver://;serverName=db02;DatabaseName=magicinfo").unwrap();
+        assert_eq!((url.host.as_str(), url.port, url.database.as_deref()), ("db02", 1433, Some("magicinfo")));
+
+        assert!(parse("jdbc:mysql://db01/magicinfo").is_err());
+    }
+}
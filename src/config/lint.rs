@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use super::syntax::{LineKind, LogicalLine};
+
+/// Keys that MagicINFO needs a value for to be able to start.
+pub const REQUIRED_KEYS: &[&str] = &["wsrm.dbVendor", "wsrm.url", "wsrm.username", "wsrm.password", "web_url", "CONTENTS_HOME"];
+
+/// A problem found in a properties file. The line is None for problems that
+/// don't belong to a single line, like a missing key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    pub line: Option<usize>,
+    pub key: Option<String>,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+/// Checks the lines of a properties file for duplicate keys, malformed lines,
+/// trailing whitespace, empty or missing required values and keys containing
+/// invisible or non-ASCII lookalike characters.
+pub fn lint(lines: &[LogicalLine], required_keys: &[&str]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut first_lines: HashMap<&str, usize> = HashMap::new();
+
+    for line in lines {
+        let (key, value) = match &line.kind {
+            LineKind::Property { key, value, .. } => (key, value),
+            LineKind::Invalid { reason } => {
+                problems.push(Problem {
+                    line: Some(line.line_number),
+                    key: None,
+                    kind: "malformed",
+                    message: format!("Line could not be parsed: {reason}"),
+                });
+                continue;
+            },
+            _ => continue,
+        };
+
+        let problem = |kind: &'static str, message: String| Problem {
+            line: Some(line.line_number),
+            key: Some(key.clone()),
+            kind,
+            message,
+        };
+
+        if let Some(first_line) = first_lines.get(key.as_str()) {
+            problems.push(problem("duplicate", format!("Duplicate key '{key}', first defined on line {first_line}. The last value wins")));
+        } else {
+            first_lines.insert(key, line.line_number);
+        }
+
+        if !line.has_explicit_separator() {
+            problems.push(problem("missing-separator", format!("Key '{key}' is not followed by '=' or ':'")));
+        }
+
+        if let Some(c) = key.chars().find(|c| is_suspicious(*c)) {
+            problems.push(problem("suspicious-character", format!("Key '{key}' contains the invisible or non-ASCII character U+{:04X}", c as u32)));
+        }
+
+        let raw_value = line.raw.trim_end_matches([' ', '\t', '\x0c']);
+        if value.ends_with([' ', '\t', '\x0c']) && !raw_value.ends_with('\\') {
+            problems.push(problem("trailing-whitespace", format!("Value of '{key}' ends with whitespace")));
+        }
+
+        if value.trim().is_empty() && required_keys.contains(&key.as_str()) {
+            problems.push(problem("empty-required", format!("Required key '{key}' has an empty value")));
+        }
+    }
+
+    for required_key in required_keys {
+        if !first_lines.contains_key(required_key) {
+            problems.push(Problem {
+                line: None,
+                key: Some(String::from(*required_key)),
+                kind: "missing-required",
+                message: format!("Required key '{required_key}' is not set"),
+            });
+        }
+    }
+
+    problems.sort_by_key(|problem| problem.line.unwrap_or(usize::MAX));
+    problems
+}
+
+/// Returns whether a character in a key is invisible or could be mistaken for
+/// another one. Escaped spaces and tabs are valid in keys, other control
+/// characters and everything outside ASCII are not expected.
+fn is_suspicious(c: char) -> bool {
+    !c.is_ascii() || (c.is_ascii_control() && !matches!(c, '\t' | '\x0c'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint;
+    use crate::config::syntax::parse;
+
+    #[test]
+    fn test_if_problems_are_found() {
+        let content = "wsrm.url=jdbc:postgresql://localhost:5432/magicinfo\nwsrm.username=\nwsrm.url = jdbc \nbroken\nkey\u{200B}=1\nescaped = \\u12\n";
+        let problems = lint(&parse(content), &["wsrm.url", "wsrm.username", "wsrm.password"]);
+        let found: Vec<(Option<usize>, &str)> = problems.iter().map(|problem| (problem.line, problem.kind)).collect();
+
+        assert_eq!(found, vec![
+            (Some(2), "empty-required"),
+            (Some(3), "duplicate"),
+            (Some(3), "trailing-whitespace"),
+            (Some(4), "missing-separator"),
+            (Some(5), "suspicious-character"),
+            (Some(6), "malformed"),
+            (None, "missing-required"),
+        ]);
+    }
+
+    #[test]
+    fn test_if_escaped_spaces_in_keys_are_allowed() {
+        let content = "display\\ name=Lobby\ntab\\tkey=1\nhom\u{0435}=2\n";
+        let problems = lint(&parse(content), &[]);
+        let found: Vec<(Option<usize>, &str)> = problems.iter().map(|problem| (problem.line, problem.kind)).collect();
+
+        assert_eq!(found, vec![(Some(3), "suspicious-character")]);
+    }
+}
@@ -1,21 +1,43 @@
+mod backup;
+mod cipher;
 mod config_util;
+mod convert;
+mod diff;
+mod interpolate;
+mod jdbc;
+mod lint;
 mod properties;
+mod query;
+mod schema;
 mod encrypted;
+mod syntax;
+mod watch;
 
 pub use config_util::get_config_properties_path;
 pub use config_util::get_mi_home_dir;
+pub use config_util::find_mi_home_dir;
 pub use config_util::get_config_properties;
+pub use cipher::{Ciphers, AUTO_CIPHER, CIPHER_NAMES};
+pub use jdbc::{parse as parse_jdbc_url, JdbcUrl, POSTGRESQL_VENDOR};
 
 use clap::ArgMatches;
+use regex::Regex;
+use serde_json::json;
 use simple_error::SimpleError;
-use std::{collections::HashMap, path::PathBuf, process::exit};
+use std::{collections::{BTreeMap, HashMap}, io::Read, path::{Path, PathBuf}, process::exit, time::Duration};
 
-use crate::utils::{print_as_json, print_as_lines, print_error};
+use crate::utils::{print_as_json, print_as_lines, print_error, redact, REDACTED_VALUE};
 
 use self::properties::PropertiesMut;
 
 const LOG_PROPERTY: &str = "repeater.log.path";
-const ENCRYPTION_KEY_PROPERTY: &str = "encrypt.manager.key.v1";
+
+/// The prefix of the encryption keys. MagicINFO stores the first key as
+/// encrypt.manager.key.v1, later versions get a higher number.
+const ENCRYPTION_KEY_PREFIX: &str = "encrypt.manager.key.v";
+
+/// The value an overlay uses to remove a key from the base configuration.
+const UNSET_SENTINEL: &str = "!unset";
 
 /// Returns the configuration values in the order in which the properties are
 /// requested
@@ -26,6 +48,16 @@ pub fn handle_config_command(submatches: &ArgMatches) {
         Some(("replace", subsubmatches)) => replace_config_value(subsubmatches),
         Some(("remove", subsubmatches)) => remove_config_value(subsubmatches),
         Some(("overlay", subsubmatches)) => overlay_config_values(subsubmatches),
+        Some(("restore", subsubmatches)) => restore_config_backup(subsubmatches),
+        Some(("rotate-key", subsubmatches)) => rotate_encryption_key(subsubmatches),
+        Some(("lint", subsubmatches)) => lint_config_file(subsubmatches),
+        Some(("validate", subsubmatches)) => validate_config_file(subsubmatches),
+        Some(("diff", subsubmatches)) => diff_config_files(subsubmatches),
+        Some(("list", subsubmatches)) => list_config_values(subsubmatches),
+        Some(("search", subsubmatches)) => search_config_values(subsubmatches),
+        Some(("export", subsubmatches)) => export_config_values(subsubmatches),
+        Some(("import", subsubmatches)) => import_config_values(subsubmatches),
+        Some(("watch", subsubmatches)) => watch_config_file(subsubmatches),
         _ => {
             unreachable!("No valid subcommand found")
         }
@@ -52,11 +84,15 @@ fn get_config_values(submatches: &ArgMatches) {
 
     let mut property_values = property_values_res.unwrap();
     if submatches.get_flag("decrypt") {
-        let encryption_key_res = get_encryption_key();
-        if let Err(e) = encryption_key_res {
+        let ciphers_res = get_ciphers(submatches);
+        if let Err(e) = ciphers_res {
             print_error(e);
         } else {
-            decrypt_hashmap(&mut property_values, &encryption_key_res.unwrap());
+            // The report goes to stderr, so the output can still be parsed.
+            let report = decrypt_hashmap(&mut property_values, &ciphers_res.unwrap());
+            for (key, action) in report {
+                eprintln!("{key}: {action}");
+            }
         }
     }
 
@@ -66,15 +102,118 @@ fn get_config_values(submatches: &ArgMatches) {
     }
 
     print_as_lines(property_values, &properties_str);
-    return;
 }
 
-/// Reads the properties-file used as base and overlays the properties from the 
-/// overlay properties-file. The resulting configuration file will be printed
-/// to stdout.
+/// Lists the config properties whose keys match one of the glob patterns or
+/// start with the prefix. Lists all properties if no filter is given.
+fn list_config_values(submatches: &ArgMatches) {
+    let patterns_res: Result<Vec<Regex>, SimpleError> = submatches.get_many::<String>("PATTERN")
+        .unwrap_or_default()
+        .map(|pattern| query::glob_to_regex(pattern))
+        .collect();
+    if let Err(e) = patterns_res {
+        print_error(e);
+        exit(1);
+    }
+
+    let patterns = patterns_res.unwrap();
+    let prefix = submatches.get_one::<String>("prefix");
+
+    let entries: Vec<query::Entry> = get_config_entries(submatches).into_iter().filter(|entry| {
+        let matches_pattern = patterns.is_empty() || patterns.iter().any(|pattern| pattern.is_match(&entry.key));
+        let matches_prefix = prefix.is_none_or(|prefix| entry.key.starts_with(prefix.as_str()));
+        matches_pattern && matches_prefix
+    }).collect();
+
+    print_entries(entries, submatches);
+}
+
+/// Searches the config properties for keys or values matching the regexes.
+fn search_config_values(submatches: &ArgMatches) {
+    let compile = |name: &str| submatches.get_one::<String>(name).map(|pattern| {
+        query::compile_regex(pattern).unwrap_or_else(|e| {
+            print_error(e);
+            exit(1);
+        })
+    });
+
+    let any_pattern = compile("PATTERN");
+    let key_pattern = compile("key");
+    let value_pattern = compile("value");
+    if any_pattern.is_none() && key_pattern.is_none() && value_pattern.is_none() {
+        print_error("Expected a pattern, a --key pattern or a --value pattern");
+        exit(1);
+    }
+
+    let entries: Vec<query::Entry> = get_config_entries(submatches).into_iter().filter(|entry| {
+        let matches_any = any_pattern.as_ref().is_none_or(|pattern| pattern.is_match(&entry.key) || pattern.is_match(&entry.value));
+        let matches_key = key_pattern.as_ref().is_none_or(|pattern| pattern.is_match(&entry.key));
+        let matches_value = value_pattern.as_ref().is_none_or(|pattern| pattern.is_match(&entry.value));
+        matches_any && matches_key && matches_value
+    }).collect();
+
+    print_entries(entries, submatches);
+}
+
+/// Returns all properties in effect in the config.properties file, decrypted
+/// if requested.
+fn get_config_entries(submatches: &ArgMatches) -> Vec<query::Entry> {
+    let lines_res = get_property_mut(submatches).and_then(|properties| properties.get_lines());
+    if let Err(e) = lines_res {
+        print_error(e);
+        exit(1);
+    }
+
+    let mut entries = query::get_entries(&lines_res.unwrap());
+    if submatches.get_flag("decrypt") {
+        let ciphers_res = get_ciphers(submatches);
+        if let Err(e) = ciphers_res {
+            print_error(e);
+            exit(1);
+        }
+
+        let mut values: HashMap<String, String> = entries.iter().map(|entry| (entry.key.clone(), entry.value.clone())).collect();
+        decrypt_values(&mut values, &ciphers_res.unwrap());
+        for entry in entries.iter_mut() {
+            entry.value = values.remove(&entry.key).unwrap_or_default();
+        }
+    }
+
+    entries
+}
+
+/// Prints the properties as `key = value` lines or as json, optionally with
+/// the line numbers they are defined on.
+fn print_entries(mut entries: Vec<query::Entry>, submatches: &ArgMatches) {
+    let show_line_numbers = submatches.get_flag("show-line-numbers");
+    for entry in entries.iter_mut() {
+        entry.value = redact(&entry.key, &entry.value);
+        if !show_line_numbers {
+            entry.line = None;
+        }
+    }
+
+    if submatches.get_flag("json") {
+        let json = serde_json::ser::to_string(&entries).unwrap();
+        println!("{}", json);
+        return;
+    }
+
+    for entry in entries {
+        match entry.line {
+            Some(line) => println!("{:>5}: {} = {}", line, entry.key, entry.value),
+            None => println!("{} = {}", entry.key, entry.value),
+        }
+    }
+}
+
+/// Reads the properties-file used as base and overlays the properties from the
+/// overlay properties-files, in the given order. An overlay can remove a key
+/// by setting it to `!unset`. The resulting configuration file will be printed
+/// to stdout, written to the output file or written in place to the base file.
 fn overlay_config_values(submatches: &ArgMatches) {
     let base_config = submatches.get_one::<String>("BASE_CONFIG_INI").unwrap();
-    let overlay_config = submatches.get_one::<String>("OVERLAY_CONFIG_INI").unwrap();
+    let overlay_configs: Vec<&String> = submatches.get_many("OVERLAY_CONFIG_INI").unwrap().collect();
 
     // Load the base configuration file
     let base_config_properties_res = PropertiesMut::open(base_config);
@@ -83,23 +222,85 @@ fn overlay_config_values(submatches: &ArgMatches) {
         exit(1);
     }
     let mut base_config_properties = base_config_properties_res.unwrap();
+    base_config_properties.set_backup_count(get_backup_count(submatches));
+
+    // Read every overlay configuration file as hashmap and apply every
+    // key-value pair on the base configuration. Later layers override earlier
+    // ones, so only the last layer touching a key is remembered.
+    let mut layer_per_key: BTreeMap<String, (&str, Option<String>)> = BTreeMap::new();
+    for overlay_config in &overlay_configs {
+        let config_changes = read_overlay(overlay_config);
+        for (config_key, config_value) in config_changes {
+            let value = if config_value == UNSET_SENTINEL { None } else { Some(config_value) };
+            layer_per_key.insert(config_key, (overlay_config.as_str(), value));
+        }
+    }
+
+    // Values from the overlays can refer to each other and to the properties
+    // of the base configuration.
+    let pending_values: HashMap<String, String> = layer_per_key.iter()
+        .filter_map(|(config_key, (_, config_value))| config_value.clone().map(|value| (config_key.clone(), value)))
+        .collect();
+    let existing_values = read_existing_values(&base_config_properties);
+    let interpolator = interpolate::Interpolator::new(&existing_values, &pending_values, submatches.get_flag("allow-unresolved"));
+
+    for (config_key, (_, config_value)) in &layer_per_key {
+        match config_value {
+            Some(value) => {
+                let value = interpolate_or_exit(&interpolator, config_key, value);
+                base_config_properties.set(config_key, &value);
+            },
+            None => base_config_properties.remove(config_key),
+        }
+    }
 
-    // Load the overlay configuration file
-    let overlay_config_properties_res = PropertiesMut::open(overlay_config);
-    if overlay_config_properties_res.is_err() {
-        print_error("Could not read the overlay properties-file.");
+    let output_path = submatches.get_one::<String>("output");
+    let in_place = submatches.get_flag("in-place");
+    let write_res = if in_place {
+        base_config_properties.write()
+    } else if let Some(output_path) = output_path {
+        base_config_properties.write_to(Path::new(output_path))
+    } else {
+        // Construct the final configuration file and print it to stdout.
+        base_config_properties.get_mutated_content().map(|content| print!("{}", content))
+    };
+
+    if let Err(e) = write_res {
+        print_error(e);
         exit(1);
     }
 
-    // Read the overlay configuration file as hashmap and apply every key-value pair on the base configuration
-    let config_changes = overlay_config_properties_res.unwrap().get_hashmap_content().unwrap();
-    config_changes.iter().for_each(|(config_key, config_value)| {
-        base_config_properties.set(config_key, config_value);
-    });
+    if submatches.get_flag("report") {
+        let report = layer_per_key.iter().map(|(config_key, (layer, config_value))| {
+            let action = if config_value.is_some() { "set by" } else { "removed by" };
+            let layer_name = if *layer == "-" { "stdin" } else { layer };
+            format!("  {config_key} {action} {layer_name}")
+        }).collect::<Vec<String>>().join("\n");
+
+        // The report can't go to stdout when the configuration file is printed
+        // there as well.
+        if in_place || output_path.is_some() {
+            println!("{}", report);
+        } else {
+            eprintln!("{}", report);
+        }
+    }
+}
+
+/// Reads the properties of an overlay file, or of stdin if the file is `-`.
+fn read_overlay(overlay_config: &str) -> HashMap<String, String> {
+    if overlay_config != "-" {
+        return read_properties_file(Path::new(overlay_config));
+    }
 
-    // Construct the final configuration file and print it to stdout.
-    let content = base_config_properties.get_mutated_content().unwrap();
-    println!("{}", content);
+    let mut content = Vec::new();
+    if let Err(e) = std::io::stdin().read_to_end(&mut content) {
+        let error_message = format!("Could not read the overlay from stdin: {e}");
+        print_error(error_message);
+        exit(1);
+    }
+
+    properties::parse_hashmap_content(&content)
 }
 
 /// Edits one config property value by doing a search and replace on it.
@@ -127,16 +328,26 @@ fn replace_config_value(submatches: &ArgMatches) {
         exit(1);
     }
 
-    let current_value = current_value_opt.clone().unwrap();
-    let new_value = current_value.replace(search, replace);
-
-    let property_res = get_property_mut();
+    let property_res = get_property_mut(submatches);
     if let Err(e) = property_res {
         print_error(e);
         exit(1);
     }
-
     let mut property = property_res.unwrap();
+
+    // Only the replacement is interpolated, the current value is used as is.
+    let no_pending_values = HashMap::new();
+    let replace = interpolate_value(&property, &no_pending_values, key, replace, submatches.get_flag("allow-unresolved"));
+
+    let current_value = current_value_opt.clone().unwrap();
+    let new_value = current_value.replace(search, &replace);
+    if !submatches.get_flag("force") {
+        if let Err(message) = get_schema(submatches).check_value(key, &new_value) {
+            print_error(schema_error(vec![message]));
+            exit(1);
+        }
+    }
+
     property.set(key, &new_value);
     let property_write_res = property.write();
     if let Err(e) = property_write_res {
@@ -145,44 +356,255 @@ fn replace_config_value(submatches: &ArgMatches) {
     }
 }
 
-/// Sets one config property value
+/// Sets one or more config property values. Accepts a single `KEY VALUE`
+/// pair or any number of `KEY=VALUE` assignments, keys to remove and a
+/// properties file with changes. All changes are written at once, or none.
 fn set_config_value(submatches: &ArgMatches) {
-    let key = submatches.get_one::<String>("KEY").unwrap();
-    let mut value = submatches.get_one::<String>("VALUE").unwrap().to_owned();
+    let assignments: Vec<&String> = submatches.get_many("ASSIGNMENT").unwrap_or_default().collect();
+    let encrypt_all = submatches.get_flag("encrypt");
+    let encrypt_keys: Vec<&String> = submatches.get_many("encrypt-keys").unwrap_or_default().collect();
+    let mut pairs: Vec<(String, Option<String>)> = Vec::new();
 
-    if key.is_empty() || value.is_empty() {
-        print_error("Expected one key and one value");
+    // Changes from the file are applied first, so the arguments can override
+    // them.
+    if let Some(changes_file) = submatches.get_one::<String>("from-file") {
+        let mut file_changes: Vec<(String, String)> = read_properties_file(Path::new(changes_file)).into_iter().collect();
+        file_changes.sort();
+        for (key, value) in file_changes {
+            let value = if value == UNSET_SENTINEL { None } else { Some(value) };
+            pairs.push((key, value));
+        }
+    }
+
+    if assignments.len() == 2 && !assignments[0].contains('=') {
+        // The single `KEY VALUE` form
+        let (key, value) = (assignments[0], assignments[1]);
+        if key.is_empty() || value.is_empty() {
+            print_error("Expected one key and one value");
+            exit(1);
+        }
+        pairs.push((key.clone(), Some(value.clone())));
+    } else {
+        for assignment in assignments {
+            let Some((key, value)) = assignment.split_once('=') else {
+                let error_message = format!("Expected KEY=VALUE, got '{assignment}'");
+                print_error(error_message);
+                exit(1);
+            };
+            pairs.push((String::from(key), Some(String::from(value))));
+        }
+    }
+
+    for key in submatches.get_many::<String>("remove").unwrap_or_default() {
+        pairs.push((key.clone(), None));
+    }
+
+    if pairs.is_empty() {
+        print_error("Expected one or more properties to set or remove");
         exit(1);
     }
 
-    let property_res = get_property_mut();
-    if let Err(e) = property_res {
+    let changes = pairs.into_iter().map(|(key, value)| ConfigChange {
+        encrypt: value.is_some() && (encrypt_all || encrypt_keys.contains(&&key)),
+        key,
+        value,
+    }).collect();
+
+    if let Err(e) = apply_config_changes(submatches, changes, true) {
         print_error(e);
         exit(1);
     }
+}
 
-    if submatches.get_flag("encrypt") {
-        let encryption_key_res = get_encryption_key();
-        if let Err(e) = encryption_key_res {
-            print_error(e);
-            exit(1);
-        }
-        let encryption_res = encrypted::aes_128_ecb_encrypt(&encryption_key_res.unwrap(), &value);
-        if let Err(e) = encryption_res {
-            print_error(e);
-            exit(1);
-        }
+/// Exports the config.properties file as json, yaml, toml or dotenv file.
+fn export_config_values(submatches: &ArgMatches) {
+    let format = convert::Format::from_name(submatches.get_one::<String>("format").unwrap()).unwrap();
+    let entries = get_config_entries(submatches);
 
-        value = encryption_res.unwrap();
+    // Secrets are masked like in all other output, unless --reveal is given.
+    let properties: Vec<(String, String)> = entries.into_iter().map(|entry| {
+        let value = redact(&entry.key, &entry.value);
+        (entry.key, value)
+    }).collect();
+
+    let export_res = convert::export(&properties, format, submatches.get_flag("nested"));
+    if let Err(e) = export_res {
+        print_error(e);
+        exit(1);
     }
 
-    let mut property = property_res.unwrap();
-    property.set(key, &value);
-    let property_write_res = property.write();
-    if let Err(e) = property_write_res {
+    let content = export_res.unwrap();
+    let write_res = match submatches.get_one::<String>("output") {
+        Some(output_path) => backup::write_atomic(Path::new(output_path), content.as_bytes()),
+        None => {
+            print!("{}", content);
+            Ok(())
+        },
+    };
+
+    if let Err(e) = write_res {
+        print_error(e);
+        exit(1);
+    }
+}
+
+/// Applies the keys and values from a json, yaml, toml or dotenv file to the
+/// config.properties file, the same way `config set` does.
+fn import_config_values(submatches: &ArgMatches) {
+    let file = submatches.get_one::<String>("FILE").unwrap();
+    let format_opt = match submatches.get_one::<String>("format") {
+        Some(format_name) => convert::Format::from_name(format_name),
+        None => convert::Format::from_path(Path::new(file)),
+    };
+
+    let Some(format) = format_opt else {
+        print_error("Could not determine the format of the file. Use --format to set it");
+        exit(1);
+    };
+
+    let mut content = String::new();
+    let read_res = if file == "-" {
+        std::io::stdin().read_to_string(&mut content).map(|_| ())
+    } else {
+        std::fs::read_to_string(file).map(|file_content| content = file_content)
+    };
+    if let Err(e) = read_res {
+        let error_message = format!("Could not read {file}: {e}");
+        print_error(error_message);
+        exit(1);
+    }
+
+    let import_res = convert::import(&content, format);
+    if let Err(e) = import_res {
         print_error(e);
         exit(1);
     }
+
+    // A masked value comes from an export without --reveal and would replace
+    // the secret with the mask.
+    let imported = import_res.unwrap();
+    let masked_keys: Vec<&str> = imported.iter()
+        .filter(|(_, value)| value.as_deref() == Some(REDACTED_VALUE))
+        .map(|(key, _)| key.as_str())
+        .collect();
+    if !masked_keys.is_empty() {
+        let error_message = format!("The values of {} are masked. Export the configuration with --reveal to import secrets", masked_keys.join(", "));
+        print_error(error_message);
+        exit(1);
+    }
+
+    let encrypt_keys: Vec<&String> = submatches.get_many("encrypt-keys").unwrap_or_default().collect();
+    let changes = imported.into_iter().map(|(key, value)| ConfigChange {
+        encrypt: encrypt_keys.contains(&&key),
+        key,
+        value,
+    }).collect();
+
+    if let Err(e) = apply_config_changes(submatches, changes, submatches.get_flag("interpolate")) {
+        print_error(e);
+        exit(1);
+    }
+}
+
+/// A change to a single key of the config.properties file. A change without a
+/// value removes the key.
+struct ConfigChange {
+    key: String,
+    value: Option<String>,
+    encrypt: bool,
+}
+
+/// Resolves the references in the new values if `interpolate` is set,
+/// encrypts them where requested and writes all changes to the
+/// config.properties file at once. Nothing is written if any of the changes is
+/// invalid.
+fn apply_config_changes(submatches: &ArgMatches, changes: Vec<ConfigChange>, interpolate: bool) -> Result<(), SimpleError> {
+    let mut property = get_property_mut(submatches)?;
+    let existing_values = property.get_hashmap_content()?;
+    let values = resolve_config_values(&changes, &existing_values, interpolate, submatches.get_flag("allow-unresolved"))?;
+
+    // All values are checked before anything is changed, so that every
+    // problem is reported at once.
+    if !submatches.get_flag("force") {
+        check_config_changes(&get_schema(submatches), &changes, &values)?;
+    }
+
+    let mut ciphers: Option<Ciphers> = None;
+    for (change, value) in changes.iter().zip(values) {
+        if change.key.is_empty() {
+            return Err(SimpleError::new("Expected a non-empty key"));
+        }
+
+        let Some(mut value) = value else {
+            property.remove(&change.key);
+            continue;
+        };
+
+        if change.encrypt {
+            if ciphers.is_none() {
+                ciphers = Some(get_ciphers(submatches)?);
+            }
+            value = ciphers.as_ref().unwrap().encrypt(&value)?;
+        }
+
+        property.set(&change.key, &value);
+    }
+
+    property.write()
+}
+
+/// Returns the values of the changes as they will be written, before they're
+/// encrypted. With `interpolate`, references are resolved against the
+/// existing values and the other new values. Otherwise the values are written
+/// exactly as given, so an exported `${...}` is imported unchanged.
+fn resolve_config_values(changes: &[ConfigChange], existing_values: &HashMap<String, String>, interpolate: bool, allow_unresolved: bool) -> Result<Vec<Option<String>>, SimpleError> {
+    if !interpolate {
+        return Ok(changes.iter().map(|change| change.value.clone()).collect());
+    }
+
+    let new_values: HashMap<String, String> = changes.iter()
+        .filter_map(|change| change.value.clone().map(|value| (change.key.clone(), value)))
+        .collect();
+
+    changes.iter().map(|change| {
+        let Some(value) = &change.value else {
+            return Ok(None);
+        };
+
+        // A reference to the key that is being set resolves to its current
+        // value, so it's left out of the pending values.
+        let mut pending_values = new_values.clone();
+        pending_values.remove(&change.key);
+        let interpolator = interpolate::Interpolator::new(existing_values, &pending_values, allow_unresolved);
+        interpolator.interpolate(&change.key, value).map(Some)
+    }).collect()
+}
+
+/// Checks the values that will be written against the schema.
+fn check_config_changes(schema: &schema::Schema, changes: &[ConfigChange], values: &[Option<String>]) -> Result<(), SimpleError> {
+    let mut messages = Vec::new();
+    for (change, value) in changes.iter().zip(values) {
+        let result = match value {
+            Some(value) => schema.check_value(&change.key, value),
+            None if schema.required_keys().any(|key| key == change.key) => Err(format!("Required key '{}' can't be removed", change.key)),
+            None => Ok(()),
+        };
+
+        if let Err(message) = result {
+            messages.push(message);
+        }
+    }
+
+    if messages.is_empty() {
+        return Ok(());
+    }
+
+    Err(schema_error(messages))
+}
+
+fn schema_error(messages: Vec<String>) -> SimpleError {
+    let message = format!("{}. Nothing was changed, use --force to write the values anyway", messages.join(". "));
+    SimpleError::new(message)
 }
 
 /// Removes a config property value
@@ -194,7 +616,13 @@ fn remove_config_value(submatches: &ArgMatches) {
         exit(1);
     }
 
-    let property_res = get_property_mut();
+    let is_required = get_schema(submatches).required_keys().any(|required_key| required_key == key);
+    if is_required && !submatches.get_flag("force") {
+        print_error(schema_error(vec![format!("Required key '{key}' can't be removed")]));
+        exit(1);
+    }
+
+    let property_res = get_property_mut(submatches);
     if let Err(e) = property_res {
         print_error(e);
         exit(1);
@@ -209,6 +637,365 @@ fn remove_config_value(submatches: &ArgMatches) {
     }
 }
 
+/// Replaces the encryption key with a new or generated key. All values that
+/// are encrypted with the old key are re-encrypted with the new key, and
+/// written together with the new key in a single update.
+fn rotate_encryption_key(submatches: &ArgMatches) {
+    let rotation_res = get_property_mut(submatches).and_then(|mut property| {
+        let values = property.get_hashmap_content()?;
+        let old_keys = get_encryption_keys(submatches, &values)?;
+        let (rotated_version, old_key) = &old_keys[0];
+
+        let generated = !submatches.contains_id("key");
+        let new_key = match submatches.get_one::<String>("key") {
+            Some(key) if key.len() < 16 => return Err(SimpleError::new("Expected an encryption key of at least 16 bytes")),
+            Some(key) if key == old_key => return Err(SimpleError::new("The new encryption key is the same as the current key")),
+            Some(key) => key.clone(),
+            None => encrypted::generate_key()?,
+        };
+
+        let mut keys: Vec<&String> = values.keys().filter(|key| encryption_key_version(key).is_none()).collect();
+        keys.sort();
+
+        // Every value is re-encrypted with the cipher it was encrypted with,
+        // also values that were encrypted with an older key version.
+        let old_ciphers = new_ciphers(submatches, &old_keys)?;
+        let new_ciphers = new_ciphers(submatches, &[(*rotated_version, new_key.clone())])?;
+        let mut rotated_keys = Vec::new();
+        let mut ambiguous_keys = Vec::new();
+        for key in keys {
+            let (decrypted, cipher_name) = match old_ciphers.classify(&values[key]) {
+                cipher::ValueKind::Encrypted(decrypted, cipher_name) => (decrypted, cipher_name),
+                cipher::ValueKind::Plaintext => continue,
+                cipher::ValueKind::Ambiguous(reason) => {
+                    ambiguous_keys.push(json!({ "key": key, "reason": reason }));
+                    continue;
+                },
+            };
+
+            let encrypted = new_ciphers.encrypt_with(cipher_name, &decrypted)?;
+            if new_ciphers.decrypt(&encrypted)? != decrypted {
+                return Err(SimpleError::new(format!("The value of '{key}' could not be re-encrypted")));
+            }
+
+            property.set(key, &encrypted);
+            rotated_keys.push(key.clone());
+        }
+
+        let key_property = format!("{ENCRYPTION_KEY_PREFIX}{rotated_version}");
+        property.set(&key_property, &new_key);
+
+        let dry_run = submatches.get_flag("dry-run");
+        if !dry_run {
+            property.write()?;
+        }
+
+        Ok(json!({
+            "dry_run": dry_run,
+            "generated_key": generated,
+            "key_property": key_property,
+            "keys": rotated_keys,
+            "ambiguous": ambiguous_keys,
+        }))
+    });
+
+    if let Err(e) = rotation_res {
+        print_error(e);
+        exit(1);
+    }
+
+    let report = rotation_res.unwrap();
+    if submatches.get_flag("json") {
+        println!("{report}");
+        return;
+    }
+
+    let keys = report["keys"].as_array().unwrap();
+    let verb = if submatches.get_flag("dry-run") { "Would re-encrypt" } else { "Re-encrypted" };
+    println!("{verb} {} value(s) with the new encryption key{}", keys.len(), if keys.is_empty() { "" } else { ":" });
+    for key in keys {
+        println!("  {}", key.as_str().unwrap());
+    }
+
+    // Values that look encrypted but don't decrypt with the old key are kept
+    // as they are, as they may be encrypted with another key.
+    for ambiguous in report["ambiguous"].as_array().unwrap() {
+        println!("Left '{}' as is, it looks encrypted but could not be decrypted: {}", ambiguous["key"].as_str().unwrap(), ambiguous["reason"].as_str().unwrap());
+    }
+}
+
+/// Lists the backups of the config.properties file, or restores the given
+/// backup. A backup can be selected by its number in the list or by its path.
+fn restore_config_backup(submatches: &ArgMatches) {
+    let config_path_res = get_config_properties_path();
+    if let Err(e) = config_path_res {
+        print_error(e);
+        exit(1);
+    }
+    let config_path = config_path_res.unwrap();
+
+    let backups_res = backup::list_backups(&config_path);
+    if let Err(e) = backups_res {
+        print_error(e);
+        exit(1);
+    }
+    let backups = backups_res.unwrap();
+
+    let Some(selection) = submatches.get_one::<String>("BACKUP") else {
+        print_backups(&backups, submatches.get_flag("json"));
+        return;
+    };
+
+    let selected_backup = backups.iter().enumerate().find(|(index, backup)| {
+        selection.parse::<usize>().ok() == Some(index + 1)
+            || backup.path.file_name().is_some_and(|name| name.to_string_lossy() == selection.as_str())
+            || backup.path.as_path() == Path::new(selection)
+    });
+
+    let Some((_, backup)) = selected_backup else {
+        let error_message = format!("Could not find backup '{selection}'. Run `magicutil config restore` to list the available backups");
+        print_error(error_message);
+        exit(1);
+    };
+
+    if let Err(e) = backup::restore_backup(&config_path, backup, get_backup_count(submatches)) {
+        print_error(e);
+        exit(1);
+    }
+
+    println!("Restored config.properties from the backup of {}", backup.timestamp);
+}
+
+/// Prints the list of backups, newest first.
+fn print_backups(backups: &[backup::Backup], json: bool) {
+    if json {
+        let json = serde_json::ser::to_string(backups).unwrap();
+        println!("{}", json);
+        return;
+    }
+
+    if backups.is_empty() {
+        println!("No backups found");
+        return;
+    }
+
+    for (index, backup) in backups.iter().enumerate() {
+        println!("  {:>2}  {}  {}", index + 1, backup.timestamp, backup.path.display());
+    }
+}
+
+/// Checks a properties file for problems and exits with a non-zero exit code
+/// if any are found. Checks the config.properties file if no file is given.
+fn lint_config_file(submatches: &ArgMatches) {
+    let file_path = get_file_argument(submatches);
+    let problems = lint::lint(&read_properties_lines(&file_path), lint::REQUIRED_KEYS);
+    print_problems(&file_path, &problems, submatches.get_flag("json"));
+}
+
+/// Checks the values of a properties file against the schema and exits with a
+/// non-zero exit code if any are invalid. Checks the config.properties file if
+/// no file is given.
+fn validate_config_file(submatches: &ArgMatches) {
+    let file_path = get_file_argument(submatches);
+    let problems = get_schema(submatches).validate(&read_properties_lines(&file_path));
+    print_problems(&file_path, &problems, submatches.get_flag("json"));
+}
+
+/// Returns the path given as FILE argument, or the path to the
+/// config.properties file.
+fn get_file_argument(submatches: &ArgMatches) -> PathBuf {
+    match submatches.get_one::<String>("FILE") {
+        Some(file) => PathBuf::from(file),
+        None => get_config_properties_path().unwrap_or_else(|e| {
+            print_error(e);
+            exit(1);
+        }),
+    }
+}
+
+/// Reads the lines of the given properties file, or exits if that fails.
+fn read_properties_lines(file_path: &Path) -> Vec<syntax::LogicalLine> {
+    let lines_res = PropertiesMut::open(file_path.to_str().unwrap()).and_then(|properties| properties.get_lines());
+    if let Err(e) = lines_res {
+        print_error(e);
+        exit(1);
+    }
+
+    lines_res.unwrap()
+}
+
+/// Prints the problems found in a file and exits with a non-zero exit code if
+/// there are any.
+fn print_problems(file_path: &Path, problems: &[lint::Problem], json: bool) {
+    if json {
+        println!("{}", json!({
+            "file": file_path,
+            "problems": problems,
+        }));
+    } else {
+        for problem in problems {
+            match problem.line {
+                Some(line) => println!("{}:{}: {}", file_path.display(), line, problem.message),
+                None => println!("{}: {}", file_path.display(), problem.message),
+            }
+        }
+
+        if problems.is_empty() {
+            println!("No problems found");
+        }
+    }
+
+    if !problems.is_empty() {
+        exit(1);
+    }
+}
+
+/// Watches a properties file and prints every change to a key as it happens,
+/// as text or json lines. Watches the config.properties file if no file is
+/// given.
+fn watch_config_file(submatches: &ArgMatches) {
+    let file_path = get_file_argument(submatches);
+    let interval = Duration::from_millis(*submatches.get_one::<u64>("interval").unwrap());
+    let json = submatches.get_flag("json");
+
+    watch::watch(&file_path, interval, |events| {
+        for mut event in events {
+            event.old = event.old.map(|value| redact(&event.key, &value));
+            event.new = event.new.map(|value| redact(&event.key, &value));
+
+            if json {
+                println!("{}", serde_json::ser::to_string(&event).unwrap());
+            } else {
+                println!("{}", event.format_text());
+            }
+        }
+    });
+}
+
+/// Compares two properties files key by key. Compares the file with the
+/// config.properties file if only one file is given.
+fn diff_config_files(submatches: &ArgMatches) {
+    let old_path = PathBuf::from(submatches.get_one::<String>("OLD").unwrap());
+    let new_path = match submatches.get_one::<String>("NEW") {
+        Some(file) => PathBuf::from(file),
+        None => get_config_properties_path().unwrap_or_else(|e| {
+            print_error(e);
+            exit(1);
+        }),
+    };
+
+    let mut old_values = read_properties_file(&old_path);
+    let mut new_values = read_properties_file(&new_path);
+
+    if submatches.get_flag("decrypt") {
+        // Every file is decrypted with its own keys, or with the keys of the
+        // other file if it doesn't contain any.
+        let has_keys = |values: &HashMap<String, String>| values.keys().any(|key| encryption_key_version(key).is_some());
+        let old_keys_source = if has_keys(&old_values) { &old_values } else { &new_values };
+        let new_keys_source = if has_keys(&new_values) { &new_values } else { &old_values };
+        if !has_keys(old_keys_source) {
+            print_error("The encryption key could not be found in either properties file.");
+            exit(1);
+        }
+
+        let ciphers_res = get_encryption_keys(submatches, old_keys_source)
+            .and_then(|old_keys| new_ciphers(submatches, &old_keys))
+            .and_then(|old_ciphers| Ok((old_ciphers, new_ciphers(submatches, &get_encryption_keys(submatches, new_keys_source)?)?)));
+        if let Err(e) = ciphers_res {
+            print_error(e);
+            exit(1);
+        }
+
+        let (old_ciphers, new_ciphers) = ciphers_res.unwrap();
+        decrypt_values(&mut old_values, &old_ciphers);
+        decrypt_values(&mut new_values, &new_ciphers);
+    }
+
+    let mut properties_diff = diff::diff(&old_values, &new_values);
+    properties_diff.redact(redact);
+    match submatches.get_one::<String>("format").map(|format| format.as_str()) {
+        Some("json") => {
+            let json = serde_json::ser::to_string(&properties_diff).unwrap();
+            println!("{}", json);
+        },
+        Some("unified") => {
+            let old_name = old_path.display().to_string();
+            let new_name = new_path.display().to_string();
+            print!("{}", diff::format_unified(&old_name, &old_values, &new_name, &new_values, redact));
+        },
+        _ => print!("{}", diff::format_text(&properties_diff)),
+    }
+}
+
+/// Reads all properties from the given file, or exits if that fails.
+fn read_properties_file(path: &Path) -> HashMap<String, String> {
+    let content_res = PropertiesMut::open(path.to_str().unwrap()).and_then(|properties| properties.get_hashmap_content());
+    if let Err(e) = content_res {
+        let error_message = format!("{}: {e}", path.display());
+        print_error(error_message);
+        exit(1);
+    }
+
+    content_res.unwrap()
+}
+
+/// Returns the newest encryption key stored in the given properties file.
+pub fn read_encryption_key(path: &Path) -> Result<String, SimpleError> {
+    let values = PropertiesMut::open(path.to_str().unwrap())?.get_hashmap_content()?;
+    find_encryption_keys(&values, None).into_iter().next().map(|(_, key)| key).ok_or_else(|| {
+        let error_message = format!("The encryption key could not be found in {}", path.display());
+        SimpleError::new(error_message)
+    })
+}
+
+/// Tries to decrypt all values in the hashmap, except for the encryption keys
+/// themselves. Values that fail to decrypt are kept as they are.
+fn decrypt_values(values: &mut HashMap<String, String>, ciphers: &Ciphers) {
+    let mut value_map: HashMap<String, Option<String>> = values.iter()
+        .filter(|(key, _)| encryption_key_version(key).is_none())
+        .map(|(key, value)| (key.clone(), Some(value.clone())))
+        .collect();
+    decrypt_hashmap(&mut value_map, ciphers);
+
+    for (key, value) in value_map {
+        values.insert(key, value.unwrap_or_default());
+    }
+}
+
+/// Resolves the `${...}` references in a value that is about to be set. Exits
+/// if a reference can't be resolved, unless unresolved references are allowed.
+fn interpolate_value(properties: &PropertiesMut, pending_values: &HashMap<String, String>, key: &str, value: &str, allow_unresolved: bool) -> String {
+    let existing_values = read_existing_values(properties);
+    let interpolator = interpolate::Interpolator::new(&existing_values, pending_values, allow_unresolved);
+    interpolate_or_exit(&interpolator, key, value)
+}
+
+/// Reads the values of the properties file that references can refer to.
+fn read_existing_values(properties: &PropertiesMut) -> HashMap<String, String> {
+    properties.get_hashmap_content().unwrap_or_else(|e| {
+        print_error(e);
+        exit(1);
+    })
+}
+
+fn interpolate_or_exit(interpolator: &interpolate::Interpolator, key: &str, value: &str) -> String {
+    interpolator.interpolate(key, value).unwrap_or_else(|e| {
+        print_error(e);
+        exit(1);
+    })
+}
+
+/// Searches in the config.properties file for the requested properties and
+/// decrypts the values that are encrypted.
+pub fn get_decrypted_config_properties(properties: &[&str]) -> Result<HashMap<String, Option<String>>, SimpleError> {
+    let mut property_values = config_util::get_config_properties(properties)?;
+    let keys: Vec<String> = find_encryption_keys(&config_util::get_config_values()?, None).into_iter().map(|(_, key)| key).collect();
+    let ciphers = Ciphers::with_keys(&keys, cipher::AUTO_CIPHER).map_err(|_| missing_key_error(None))?;
+    decrypt_hashmap(&mut property_values, &ciphers);
+
+    Ok(property_values)
+}
+
 /// Returns the log directory path
 pub fn get_log_directory() -> PathBuf {
     let property_values_res = config_util::get_config_properties(&[LOG_PROPERTY]);
@@ -229,40 +1016,133 @@ pub fn get_log_directory() -> PathBuf {
 }
 
 /// Returns the PropertiesMut from the MagicINFO's main config.properties file.
-fn get_property_mut() -> Result<PropertiesMut, SimpleError>{
+fn get_property_mut(submatches: &ArgMatches) -> Result<PropertiesMut, SimpleError>{
     let config_path = get_config_properties_path()?;
     let config_path_str = config_path.to_str().unwrap();
-    let property_mut = properties::PropertiesMut::open(config_path_str)?;
+    let mut property_mut = properties::PropertiesMut::open(config_path_str)?;
+    property_mut.set_backup_count(get_backup_count(submatches));
 
     Ok(property_mut)
 }
 
-// Tries to decrypt all values in the hashmap. If a value fails, it will be skipped.
-fn decrypt_hashmap(map: &mut HashMap<String, Option<String>>, encryption_key: &str) {
-    let mut mutations: HashMap<String, String> = HashMap::new();
-    for (key, value_opt) in map.iter() {
-        if value_opt.is_some() {
-            let value = value_opt.clone().unwrap();
-            let decryption_res = encrypted::aes_128_ecb_decrypt(encryption_key, &value);
-            if let Ok(decrypted_string) = decryption_res {
-                mutations.insert(key.clone(), decrypted_string);
-            }
+/// Returns the built-in schema, extended with the schema file given with
+/// --schema. Exits if the schema file can't be read.
+fn get_schema(submatches: &ArgMatches) -> schema::Schema {
+    let mut schema = schema::Schema::builtin();
+    if let Some(schema_file) = submatches.get_one::<String>("schema") {
+        if let Err(e) = schema.extend_from_file(Path::new(schema_file)) {
+            print_error(e);
+            exit(1);
         }
     }
 
-    for (mutation_key, mutation_value) in mutations {
-        map.insert(mutation_key, Some(mutation_value));
+    schema
+}
+
+/// Returns the number of backups to keep when writing the config.properties.
+fn get_backup_count(submatches: &ArgMatches) -> usize {
+    submatches.get_one::<usize>("backups").copied().unwrap_or(backup::DEFAULT_BACKUP_COUNT)
+}
+
+/// Decrypts the values in the hashmap that are encrypted with the key. Values
+/// that are not, or not clearly, encrypted are left as they are. Returns per
+/// key what was done with its value.
+fn decrypt_hashmap(map: &mut HashMap<String, Option<String>>, ciphers: &Ciphers) -> BTreeMap<String, String> {
+    let mut report = BTreeMap::new();
+    for (key, value_opt) in map.iter_mut() {
+        let Some(value) = value_opt else {
+            report.insert(key.clone(), String::from("not set"));
+            continue;
+        };
+
+        let action = match ciphers.classify(value) {
+            cipher::ValueKind::Encrypted(decrypted, cipher_name) => {
+                *value = decrypted;
+                format!("decrypted ({cipher_name})")
+            },
+            cipher::ValueKind::Plaintext => String::from("not encrypted, left as is"),
+            cipher::ValueKind::Ambiguous(reason) => format!("looks encrypted but could not be decrypted, left as is ({reason})"),
+        };
+        report.insert(key.clone(), action);
     }
+
+    report
+}
+
+/// Returns the ciphers selected with --cipher, using the encryption keys from
+/// the config.properties file.
+fn get_ciphers(submatches: &ArgMatches) -> Result<Ciphers, SimpleError> {
+    let keys = get_encryption_keys(submatches, &config_util::get_config_values()?)?;
+    new_ciphers(submatches, &keys)
 }
 
-// Searches for the encryption key in the config.properties file.
-fn get_encryption_key() -> Result<String, SimpleError> {
-    let config_list = config_util::get_config_properties(&[ENCRYPTION_KEY_PROPERTY])?;
-    let encryption_key_opt = config_list.get(ENCRYPTION_KEY_PROPERTY).unwrap().clone();
+/// Returns the ciphers selected with --cipher, using the given keys.
+fn new_ciphers(submatches: &ArgMatches, keys: &[(u32, String)]) -> Result<Ciphers, SimpleError> {
+    let cipher_name = submatches.get_one::<String>("cipher").map(|name| name.as_str()).unwrap_or(cipher::AUTO_CIPHER);
+    let keys: Vec<String> = keys.iter().map(|(_, key)| key.clone()).collect();
+    Ciphers::with_keys(&keys, cipher_name)
+}
 
-    if encryption_key_opt.is_none() {
-        return Err(SimpleError::new("The encryption key could not be found in the config.properties."));
+/// Returns the encryption keys in the values, newest version first, or only
+/// the version selected with --key-version. Fails if there is none.
+fn get_encryption_keys(submatches: &ArgMatches, values: &HashMap<String, String>) -> Result<Vec<(u32, String)>, SimpleError> {
+    let key_version = submatches.get_one::<u32>("key-version").copied();
+    let keys = find_encryption_keys(values, key_version);
+    if keys.is_empty() {
+        return Err(missing_key_error(key_version));
     }
 
-    Ok(encryption_key_opt.unwrap())
+    Ok(keys)
+}
+
+/// Returns the encryption keys in the values with their version, newest
+/// version first. Only returns the given version if there is one.
+fn find_encryption_keys(values: &HashMap<String, String>, key_version: Option<u32>) -> Vec<(u32, String)> {
+    let mut keys: Vec<(u32, String)> = values.iter()
+        .filter_map(|(key, value)| encryption_key_version(key).map(|version| (version, value.clone())))
+        .filter(|(version, _)| key_version.is_none_or(|key_version| key_version == *version))
+        .collect();
+    keys.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+    keys
+}
+
+/// Returns the version of an encryption key property like
+/// encrypt.manager.key.v2, or None if the key is not an encryption key.
+fn encryption_key_version(key: &str) -> Option<u32> {
+    let version = key.strip_prefix(ENCRYPTION_KEY_PREFIX)?;
+    match version.bytes().all(|byte| byte.is_ascii_digit()) {
+        true => version.parse().ok(),
+        false => None,
+    }
+}
+
+fn missing_key_error(key_version: Option<u32>) -> SimpleError {
+    match key_version {
+        Some(version) => SimpleError::new(format!("The encryption key {ENCRYPTION_KEY_PREFIX}{version} could not be found in the config.properties.")),
+        None => SimpleError::new("The encryption key could not be found in the config.properties."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::{convert, resolve_config_values, ConfigChange};
+
+    #[test]
+    fn test_if_imported_values_are_written_as_exported() {
+        let properties = vec![
+            (String::from("log.path"), String::from("${catalina.home}/logs")),
+            (String::from("web_url"), String::from("$${literal}")),
+        ];
+        let exported = convert::export(&properties, convert::Format::Json, false).unwrap();
+        let changes: Vec<ConfigChange> = convert::import(&exported, convert::Format::Json).unwrap()
+            .into_iter()
+            .map(|(key, value)| ConfigChange { key, value, encrypt: false })
+            .collect();
+
+        let values = resolve_config_values(&changes, &HashMap::new(), false, false).unwrap();
+        let imported: Vec<(String, String)> = changes.iter().zip(values).map(|(change, value)| (change.key.clone(), value.unwrap())).collect();
+        assert_eq!(imported, properties);
+        assert!(resolve_config_values(&changes, &HashMap::new(), true, false).is_err());
+    }
 }
\ No newline at end of file
@@ -1,23 +1,41 @@
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufRead, Write};
-use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::Path;
 use simple_error::SimpleError;
 
-use crate::utils::print_error;
-
-lazy_static! {
-    pub static ref ENV_REG: Regex = Regex::new(r"^\$\{([a-zA-Z0-9_]+?)\}$").unwrap();
-}
+use super::backup;
+use super::syntax::{self, format_property, LineKind, LogicalLine};
 
 #[cfg(windows)]
-const LINE_ENDING: &'static str = "\r\n";
+const LINE_ENDING: &str = "\r\n";
 #[cfg(not(windows))]
-const LINE_ENDING: &'static str = "\n";
+const LINE_ENDING: &str = "\n";
+
+const DEFAULT_SEPARATOR: &str = " = ";
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The character encoding a properties file was read with, so it can be
+/// written back in the same encoding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Utf8,
+    Utf8WithBom,
+    Latin1,
+}
+
+/// A parsed properties file together with the formatting conventions used
+/// in it.
+struct Document {
+    lines: Vec<LogicalLine>,
+    encoding: Encoding,
+    line_ending: String,
+    separator: String,
+}
 
 pub struct PropertiesMut {
     source: String,
     mutations: HashMap<String, Option<String>>,
+    backup_count: usize,
 }
 
 impl PropertiesMut {
@@ -33,29 +51,19 @@ impl PropertiesMut {
         Ok(Self {
             source: String::from(path),
             mutations: HashMap::new(),
+            backup_count: backup::DEFAULT_BACKUP_COUNT,
         })
     }
 
+    /// Sets how many backups of the file are kept when writing it. Zero
+    /// disables backups.
+    pub fn set_backup_count(&mut self, backup_count: usize) {
+        self.backup_count = backup_count;
+    }
+
     /// Adds a mutation with a new value for the current PropertiesMut
     pub fn set(&mut self, key: &str, value: &str) {
-        let value_opt;
-
-        // Check if the value is of format '${ENV_VARIABLE}'. If so, we will try
-        // to insert the values from the environment variable.
-        let matches_opt = ENV_REG.captures(value);
-        if let Some(matches) = matches_opt {
-            let env_key = &matches[1];
-            if let Ok(env_val) = std::env::var(env_key) {
-                value_opt = Some(env_val);
-            } else {
-                let error_message = format!("Value for key '{}' could not be inserted from environment variables because environment variable '{}' could not be resolved. An empty string will be used as value.", key, env_key);
-                print_error(error_message);
-                value_opt = Some(String::new());
-            }
-        } else {
-            value_opt = Some(String::from(value));
-        }
-        self.mutations.insert(String::from(key), value_opt);
+        self.mutations.insert(String::from(key), Some(String::from(value)));
     }
 
     /// Adds a mutations that will remove a certain key from the PropertiesMut
@@ -63,164 +71,272 @@ impl PropertiesMut {
         self.mutations.insert(String::from(key), None);
     }
 
+    /// Returns all logical lines of the properties file, in the order in which
+    /// they appear.
+    pub fn get_lines(&self) -> Result<Vec<LogicalLine>, SimpleError> {
+        Ok(self.read_document()?.lines)
+    }
+
     /// Returns a hashmap containing all values from the config.properties file.
     pub fn get_hashmap_content(&self) -> Result<HashMap<String, String>, SimpleError> {
-        let file = File::open(&self.source).map_err(|e| {
-            let message = format!("Could not load properties file: {e}");
-            SimpleError::new(message)
-        })?;
-
         let mut content_map = HashMap::new();
 
-        for line_res in BufReader::new(file).lines() {
-            let line = line_res.map_err(|e| {
-                let message = format!("Could not load properties file: {e}");
-                SimpleError::new(message)
-            })?;
-
-            let key_opt = self.get_key_values(&line);
-            if key_opt.is_none() {
-                continue;
+        for line in self.read_document()?.lines {
+            if let LineKind::Property { key, value, .. } = line.kind {
+                content_map.insert(key, value);
             }
-
-            let (key, value) = key_opt.unwrap();
-            content_map.insert(key, value);
         }
 
         Ok(content_map)
     }
 
     /// Returns the content of the config.properties file with the mutations
-    /// applied as a string.
+    /// applied as a string. Lines that are not mutated are returned exactly as
+    /// they were, mutated lines keep their indentation, separator and line
+    /// ending.
     pub fn get_mutated_content(&self) -> Result<String, SimpleError> {
-        let mut property_buffer = String::new();
-        let mut mutations_clone = self.mutations.clone();
+        let document = self.read_document()?;
+        Ok(self.apply_mutations(&document))
+    }
 
-        let file = File::open(&self.source).map_err(|e| {
-            let message = format!("Could not load properties file: {e}");
-            SimpleError::new(message)
-        })?;
+    /// Writes the content of the config.properties file with the mutations
+    /// applied to the original config.properties file. The current file is
+    /// backed up first and then atomically replaced, so an interrupted write
+    /// never leaves a truncated file behind.
+    pub fn write(&self) -> Result<(), SimpleError> {
+        let document = self.read_document()?;
+        let content = encode(&self.apply_mutations(&document), document.encoding);
 
-        // Read the file line by line and try to interpret it as a properties
-        // key-value.
-        for line_res in BufReader::new(file).lines() {
-            let line = line_res.map_err(|e| {
-                let message = format!("Could not load properties file: {e}");
-                SimpleError::new(message)
-            })?;
+        let path = Path::new(&self.source);
+        backup::create_backup(path, self.backup_count)?;
+        backup::write_atomic(path, &content)
+    }
 
-            let key_opt = self.get_key(&line);
-            if let Some(key) = key_opt {
-                if mutations_clone.contains_key(&key) {
+    /// Writes the content of the properties file with the mutations applied to
+    /// another file, leaving the original file untouched.
+    pub fn write_to(&self, path: &Path) -> Result<(), SimpleError> {
+        let document = self.read_document()?;
+        let content = encode(&self.apply_mutations(&document), document.encoding);
+
+        backup::write_atomic(path, &content)
+    }
+
+    /// Applies the mutations on the lines of the document and returns the
+    /// resulting content.
+    fn apply_mutations(&self, document: &Document) -> String {
+        let mut entries: Vec<OutputLine> = Vec::new();
+        let mut resolved_keys: HashSet<&str> = HashSet::new();
+
+        for line in &document.lines {
+            if let Some(key) = line.key() {
+                if let Some((mutated_key, mutated_value)) = self.mutations.get_key_value(key) {
+                    resolved_keys.insert(mutated_key);
 
                     // `mutated_value` will be an option. If the key was removed
                     // with the `remove` method, it will be None. Else it will
                     // contain a value that should be the new value of this key.
-                    let mutated_value = mutations_clone.get(&key).unwrap().clone();
-                    if mutated_value.is_some() {
-                        let value = mutated_value.unwrap();
-                        let new_value = format!("{key} = {value}{LINE_ENDING}");
-                        property_buffer.push_str(&new_value);
+                    if let Some(value) = mutated_value {
+                        entries.push(OutputLine {
+                            key: Some(mutated_key),
+                            text: line.with_value(value, &document.separator),
+                            ending: &line.ending,
+                        });
                     }
 
-                    mutations_clone.remove(&key);
                     continue;
                 }
             }
 
             // All non-matching lines will be placed back in the file without
             // being changed.
-            let old_value = format!("{line}{LINE_ENDING}");
-            property_buffer.push_str(&old_value);
+            entries.push(OutputLine {
+                key: line.key(),
+                text: line.raw.clone(),
+                ending: &line.ending,
+            });
         }
 
-        // All properties that were not resolved by the iterating the values of
-        // the properties file will be added on the bottom of the file.
-        for extra_properties in mutations_clone {
-            let mutated_value = extra_properties.1;
-            if mutated_value.is_some() {
-                let key = extra_properties.0;
-                let value = mutated_value.unwrap();
-                let new_value = format!("{key} = {value}{LINE_ENDING}");
-                property_buffer.push_str(&new_value);
-            }
+        // All properties that were not resolved by iterating the lines of the
+        // properties file are new. They are added in alphabetical order,
+        // directly after the last line sharing the longest dotted prefix with
+        // them, or at the bottom of the file if no line does.
+        let mut new_properties: Vec<(&String, &String)> = self.mutations.iter()
+            .filter(|(key, _)| !resolved_keys.contains(key.as_str()))
+            .filter_map(|(key, value)| value.as_ref().map(|value| (key, value)))
+            .collect();
+        new_properties.sort();
+
+        for (key, value) in new_properties {
+            let position = find_insert_position(&entries, key);
+            entries.insert(position, OutputLine {
+                key: Some(key),
+                text: format_property(key, &document.separator, value),
+                ending: &document.line_ending,
+            });
+        }
 
+        let mut property_buffer = String::new();
+        let last_index = entries.len().saturating_sub(1);
+        for (index, entry) in entries.iter().enumerate() {
+            property_buffer.push_str(&entry.text);
+
+            // A last line without line ending needs one when it no longer is
+            // the last line.
+            if entry.ending.is_empty() && index != last_index {
+                property_buffer.push_str(&document.line_ending);
+            } else {
+                property_buffer.push_str(entry.ending);
+            }
         }
 
-        Ok(property_buffer)
+        property_buffer
     }
 
-    /// Writes the content of the config.properties file with the mutations
-    /// applied to the original config.properties file.
-    pub fn write(&self) -> Result<(), SimpleError> {
-        let content = self.get_mutated_content()?;
+    /// Reads the properties file and parses it into logical lines.
+    fn read_document(&self) -> Result<Document, SimpleError> {
+        let bytes = fs::read(&self.source).map_err(|e| {
+            let message = format!("Could not load properties file: {e}");
+            SimpleError::new(message)
+        })?;
 
-        let file_res = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.source);
+        let (content, encoding) = decode(&bytes);
+        let lines = syntax::parse(&content);
+        let line_ending = most_common(lines.iter().map(|line| line.ending.as_str()).filter(|ending| !ending.is_empty()))
+            .unwrap_or(LINE_ENDING);
+        let separator = most_common(lines.iter().filter_map(|line| line.separator()))
+            .unwrap_or(DEFAULT_SEPARATOR);
+
+        Ok(Document {
+            line_ending: String::from(line_ending),
+            separator: String::from(separator),
+            lines,
+            encoding,
+        })
+    }
+}
 
-        if let Err(e) = file_res {
-            let message = format!("Could not write properties file: {e}");
-            return Err(SimpleError::new(message));
-        }
+/// Returns a hashmap containing all values from properties content that was
+/// not read from a file, like stdin.
+pub fn parse_hashmap_content(bytes: &[u8]) -> HashMap<String, String> {
+    let (content, _) = decode(bytes);
+    syntax::parse(&content).into_iter().filter_map(|line| match line.kind {
+        LineKind::Property { key, value, .. } => Some((key, value)),
+        _ => None,
+    }).collect()
+}
 
-        let mut file = file_res.unwrap();
-        if let Err(e) = file.write_all(content.as_bytes()) {
-            let message = format!("Could not write properties file: {e}");
-            return Err(SimpleError::new(message));
-        }
+/// A line of the output while applying mutations.
+struct OutputLine<'a> {
+    key: Option<&'a str>,
+    text: String,
+    ending: &'a str,
+}
 
-        Ok(())
+/// Returns the index after the last line whose key shares the most leading
+/// dotted segments with the given key, or the end if no key shares any.
+fn find_insert_position(entries: &[OutputLine], key: &str) -> usize {
+    let mut position = entries.len();
+    let mut best_shared = 0;
+
+    for (index, entry) in entries.iter().enumerate() {
+        let Some(entry_key) = entry.key else {
+            continue;
+        };
+
+        let shared = key.split('.').zip(entry_key.split('.')).take_while(|(a, b)| a == b).count();
+        if shared > 0 && shared >= best_shared {
+            best_shared = shared;
+            position = index + 1;
+        }
     }
 
-    /// A private method that tries to find the properties key from the given
-    /// line. Returns an Option containing the key or None if no key was found.
-    fn get_key(&self, line: &str) -> Option<String> {
-        let trimmed_line = line.trim();
-        if trimmed_line.is_empty() || trimmed_line.starts_with("#") || trimmed_line.starts_with("!") {
-            return None;
-        }
+    position
+}
 
-        if !trimmed_line.contains("=") {
-            return None;
+/// Decodes the content of a properties file. Files are read as UTF-8, with or
+/// without a byte order mark, and fall back to ISO-8859-1 like Java does.
+fn decode(bytes: &[u8]) -> (String, Encoding) {
+    if let Some(without_bom) = bytes.strip_prefix(UTF8_BOM) {
+        if let Ok(content) = std::str::from_utf8(without_bom) {
+            return (String::from(content), Encoding::Utf8WithBom);
         }
+    }
 
-        let splitted_line = trimmed_line.split("=").collect::<Vec<&str>>();
-        let splitted_key = splitted_line[0].trim();
-        if splitted_key.is_empty() {
-            return None;
-        }
+    if let Ok(content) = std::str::from_utf8(bytes) {
+        return (String::from(content), Encoding::Utf8);
+    }
 
-        Some(String::from(splitted_key))
+    let content = bytes.iter().map(|byte| *byte as char).collect();
+    (content, Encoding::Latin1)
+}
+
+/// Encodes the content of a properties file in the given encoding.
+fn encode(content: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Utf8 => Vec::from(content.as_bytes()),
+        Encoding::Utf8WithBom => [UTF8_BOM, content.as_bytes()].concat(),
+
+        // Decoded Latin-1 content only contains characters up to U+00FF and
+        // new values are escaped to ASCII, so every character fits in a byte.
+        Encoding::Latin1 => content.chars().map(|c| c as u32 as u8).collect(),
     }
+}
 
-    /// A private method that tries to find the properties key/values from the
-    /// given line. Returns an Option containing the key or None if no key was
-    /// found.
-    fn get_key_values(&self, line: &str) -> Option<(String, String)> {
-        let trimmed_line = line.trim();
-        if trimmed_line.is_empty() || trimmed_line.starts_with("#") || trimmed_line.starts_with("!") {
-            return None;
+/// Returns the most common item, preferring the item seen first on a tie.
+fn most_common<'a>(items: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for item in items {
+        match counts.iter_mut().find(|(seen, _)| *seen == item) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((item, 1)),
         }
+    }
 
-        if !trimmed_line.contains("=") {
-            return None;
+    let mut best: Option<(&str, usize)> = None;
+    for (item, count) in counts {
+        if best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((item, count));
         }
+    }
 
-        let splitted_line = trimmed_line.split("=").collect::<Vec<&str>>();
-        let splitted_key = splitted_line[0].trim();
-        if splitted_key.is_empty() {
-            return None;
-        }
+    best.map(|(item, _)| item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PropertiesMut;
 
-        let key = String::from(splitted_key);
-        let value = splitted_line[1..]
-            .join("=")
-            .trim()
-            .to_owned();
+    fn mutate(content: &str, test_name: &str, mutate: impl Fn(&mut PropertiesMut)) -> String {
+        let path = std::env::temp_dir().join(format!("magicutil-{test_name}-{}.properties", std::process::id()));
+        std::fs::write(&path, content).unwrap();
 
-        Some((key, value))
+        let mut properties = PropertiesMut::open(path.to_str().unwrap()).unwrap();
+        mutate(&mut properties);
+        let mutated_content = properties.get_mutated_content().unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        mutated_content
+    }
+
+    #[test]
+    fn test_if_only_the_mutated_value_changes() {
+        let content = "# Database\r\n  wsrm.url\t: jdbc:postgresql://localhost:5432/magicinfo\r\nwsrm.username=admin\r\n";
+        let mutated = mutate(content, "value", |properties| {
+            properties.set("wsrm.url", "jdbc:postgresql://db:5432/magicinfo");
+        });
+
+        assert_eq!(mutated, "# Database\r\n  wsrm.url\t: jdbc:postgresql://db:5432/magicinfo\r\nwsrm.username=admin\r\n");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_if_new_keys_are_placed_next_to_their_prefix() {
+        let content = "device.log4j.on=false\ndevice.log4j.level=INFO\nwsrm.url=jdbc\n";
+        let mutated = mutate(content, "placement", |properties| {
+            properties.set("zz.key", "3");
+            properties.set("device.log4j.path", "2");
+            properties.set("device.log4j.max", "1");
+        });
+
+        assert_eq!(mutated, "device.log4j.on=false\ndevice.log4j.level=INFO\ndevice.log4j.max=1\ndevice.log4j.path=2\nwsrm.url=jdbc\nzz.key=3\n");
+    }
+}
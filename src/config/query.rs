@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use regex::Regex;
+use simple_error::SimpleError;
+
+use super::syntax::{LineKind, LogicalLine};
+
+/// A property as it is in effect, together with the line it is defined on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Entry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub key: String,
+    pub value: String,
+}
+
+/// Returns the properties in the order in which they appear in the file. For
+/// duplicate keys only the last definition is returned, as that one wins.
+pub fn get_entries(lines: &[LogicalLine]) -> Vec<Entry> {
+    let mut last_lines: HashMap<&str, usize> = HashMap::new();
+    for line in lines {
+        if let Some(key) = line.key() {
+            last_lines.insert(key, line.line_number);
+        }
+    }
+
+    lines.iter().filter_map(|line| match &line.kind {
+        LineKind::Property { key, value, .. } if last_lines.get(key.as_str()) == Some(&line.line_number) => Some(Entry {
+            line: Some(line.line_number),
+            key: key.clone(),
+            value: value.clone(),
+        }),
+        _ => None,
+    }).collect()
+}
+
+/// Converts a glob pattern, in which `*` matches any text and `?` matches a
+/// single character, into a regex matching the complete key.
+pub fn glob_to_regex(glob: &str) -> Result<Regex, SimpleError> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    compile_regex(&pattern)
+}
+
+/// Compiles a regex given by the user.
+pub fn compile_regex(pattern: &str) -> Result<Regex, SimpleError> {
+    Regex::new(pattern).map_err(|e| {
+        let message = format!("Invalid pattern '{pattern}': {e}");
+        SimpleError::new(message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_entries, glob_to_regex};
+    use crate::config::syntax::parse;
+
+    #[test]
+    fn test_if_globs_match_keys() {
+        let glob = glob_to_regex("device.log4j.*").unwrap();
+        assert!(glob.is_match("device.log4j.level"));
+        assert!(!glob.is_match("device_log4j.level"));
+        assert!(!glob.is_match("other.device.log4j.level"));
+    }
+
+    #[test]
+    fn test_if_only_the_last_duplicate_is_returned() {
+        let entries = get_entries(&parse("a=1\nb=2\na=3\n"));
+        let found: Vec<(Option<usize>, &str, &str)> = entries.iter().map(|entry| (entry.line, entry.key.as_str(), entry.value.as_str())).collect();
+        assert_eq!(found, vec![(Some(2), "b", "2"), (Some(3), "a", "3")]);
+    }
+}
@@ -0,0 +1,300 @@
+use std::{collections::BTreeMap, fs, path::Path};
+use regex::Regex;
+use simple_error::SimpleError;
+
+use super::jdbc;
+use super::lint::Problem;
+use super::query::compile_regex;
+use super::syntax::{LineKind, LogicalLine};
+use crate::utils::redact;
+
+lazy_static! {
+    static ref URL_REGEX: Regex = Regex::new(r"^(?i)[a-z][a-z0-9+.-]*://([^/:?#\s]+)(?::([0-9]+))?([/?#]\S*)?$").unwrap();
+    static ref JDBC_REGEX: Regex = Regex::new(r"^jdbc:([a-zA-Z0-9]+):(.+)$").unwrap();
+}
+
+/// The type of a configuration value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValueType {
+    #[default]
+    String,
+    Integer,
+    Port,
+    Bool,
+    Url,
+    JdbcUrl,
+    Path,
+    Enum,
+}
+
+/// The rule for the value of a single key.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyRule {
+    #[serde(rename = "type", default)]
+    pub value_type: ValueType,
+
+    /// The allowed values of an enum. Compared case-insensitively.
+    #[serde(default)]
+    pub values: Vec<String>,
+
+    /// The allowed range of an integer or port.
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+
+    #[serde(default)]
+    pub required: bool,
+
+    /// A regex the complete value has to match, in addition to the type.
+    pub pattern: Option<String>,
+}
+
+/// Describes the type, range and presence of well-known configuration keys.
+/// Keys without a rule can have any value.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    rules: BTreeMap<String, KeyRule>,
+}
+
+impl Schema {
+    /// Returns the built-in schema for MagicINFO's config.properties file.
+    pub fn builtin() -> Self {
+        let rule = |value_type: ValueType, required: bool| KeyRule {
+            value_type,
+            required,
+            ..KeyRule::default()
+        };
+        let enum_rule = |values: &[&str], required: bool| KeyRule {
+            value_type: ValueType::Enum,
+            values: values.iter().map(|value| String::from(*value)).collect(),
+            required,
+            ..KeyRule::default()
+        };
+
+        let rules = BTreeMap::from([
+            (String::from("wsrm.dbVendor"), enum_rule(&["PostgreSQL", "MSSQL"], true)),
+            (String::from("wsrm.url"), rule(ValueType::JdbcUrl, true)),
+            (String::from("wsrm.username"), rule(ValueType::String, true)),
+            (String::from("wsrm.password"), rule(ValueType::String, true)),
+            (String::from("web_url"), rule(ValueType::Url, true)),
+            (String::from("CONTENTS_HOME"), rule(ValueType::Path, true)),
+            (String::from("listen.port"), rule(ValueType::Port, false)),
+            (String::from("repeater.log.path"), rule(ValueType::Path, false)),
+            (String::from("device.log4j.level"), enum_rule(&["OFF", "FATAL", "ERROR", "WARN", "INFO", "DEBUG", "TRACE", "ALL"], false)),
+        ]);
+
+        Self { rules }
+    }
+
+    /// Adds the rules from a schema file, replacing built-in rules for the
+    /// same keys. The file maps keys to rules, in toml, yaml or json.
+    pub fn extend_from_file(&mut self, path: &Path) -> Result<(), SimpleError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            let message = format!("Could not read the schema file {}: {e}", path.display());
+            SimpleError::new(message)
+        })?;
+
+        let is_toml = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+        let rules_res: Result<BTreeMap<String, KeyRule>, String> = if is_toml {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            // Yaml is a superset of json, so this reads both.
+            serde_yaml::from_str(&content).map_err(|e| e.to_string())
+        };
+
+        let rules = rules_res.map_err(|e| {
+            let message = format!("Could not parse the schema file {}: {e}", path.display());
+            SimpleError::new(message)
+        })?;
+
+        for (key, rule) in rules {
+            if let Some(pattern) = &rule.pattern {
+                compile_regex(pattern)?;
+            }
+            if rule.value_type == ValueType::Enum && rule.values.is_empty() {
+                return Err(SimpleError::new(format!("The enum rule for '{key}' has no values")));
+            }
+            self.rules.insert(key, rule);
+        }
+
+        Ok(())
+    }
+
+    /// Checks a single value against the rule for its key. Returns a message
+    /// describing the problem if the value is invalid.
+    pub fn check_value(&self, key: &str, value: &str) -> Result<(), String> {
+        let Some(rule) = self.rules.get(key) else {
+            return Ok(());
+        };
+
+        if value.trim().is_empty() {
+            return match rule.required {
+                true => Err(format!("Required key '{key}' has an empty value")),
+                false => Ok(()),
+            };
+        }
+
+        // The value is redacted in the message, since the key can be a secret.
+        let shown_value = redact(key, value);
+        check_type(rule, value).map_err(|e| format!("Invalid value '{shown_value}' for '{key}': {e}"))?;
+
+        if let Some(pattern) = &rule.pattern {
+            let regex = compile_regex(&format!("^(?:{pattern})$")).map_err(|e| e.to_string())?;
+            if !regex.is_match(value) {
+                return Err(format!("Invalid value '{shown_value}' for '{key}': expected a value matching '{pattern}'"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the keys that need to have a value.
+    pub fn required_keys(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().filter(|(_, rule)| rule.required).map(|(key, _)| key.as_str())
+    }
+
+    /// Checks all values of a properties file and whether the required keys
+    /// are present.
+    pub fn validate(&self, lines: &[LogicalLine]) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        for line in lines {
+            let LineKind::Property { key, value, .. } = &line.kind else {
+                continue;
+            };
+
+            if let Err(message) = self.check_value(key, value) {
+                problems.push(Problem {
+                    line: Some(line.line_number),
+                    key: Some(key.clone()),
+                    kind: "invalid-value",
+                    message,
+                });
+            }
+        }
+
+        for required_key in self.required_keys() {
+            if !lines.iter().any(|line| line.key() == Some(required_key)) {
+                problems.push(Problem {
+                    line: None,
+                    key: Some(String::from(required_key)),
+                    kind: "missing-required",
+                    message: format!("Required key '{required_key}' is not set"),
+                });
+            }
+        }
+
+        problems
+    }
+}
+
+/// Checks whether the value matches the type and range of the rule.
+fn check_type(rule: &KeyRule, value: &str) -> Result<(), String> {
+    match rule.value_type {
+        ValueType::String => Ok(()),
+        ValueType::Integer => {
+            let number: i64 = value.parse().map_err(|_| String::from("expected a whole number"))?;
+            check_range(rule, number)
+        },
+        ValueType::Port => {
+            let port: u16 = value.parse().ok().filter(|port| *port > 0).ok_or_else(|| String::from("expected a port number between 1 and 65535"))?;
+            check_range(rule, i64::from(port))
+        },
+        ValueType::Bool => match value.to_lowercase().as_str() {
+            "true" | "false" => Ok(()),
+            _ => Err(String::from("expected true or false")),
+        },
+        ValueType::Url => {
+            let captures = URL_REGEX.captures(value).ok_or_else(|| String::from("expected a url like https://host:port/path"))?;
+            check_port(captures.get(2).map(|port| port.as_str()))
+        },
+        ValueType::JdbcUrl => check_jdbc_url(value),
+        ValueType::Path => match value.chars().find(|c| c.is_control() || "<>\"|?*".contains(*c)) {
+            Some(c) => Err(format!("a path can't contain '{}'", c.escape_default())),
+            None => Ok(()),
+        },
+        ValueType::Enum => match rule.values.iter().any(|allowed| allowed.eq_ignore_ascii_case(value)) {
+            true => Ok(()),
+            false => Err(format!("expected one of {}", rule.values.join(", "))),
+        },
+    }
+}
+
+fn check_range(rule: &KeyRule, number: i64) -> Result<(), String> {
+    match (rule.min, rule.max) {
+        (Some(min), _) if number < min => Err(format!("expected at least {min}")),
+        (_, Some(max)) if number > max => Err(format!("expected at most {max}")),
+        _ => Ok(()),
+    }
+}
+
+fn check_port(port: Option<&str>) -> Result<(), String> {
+    match port.map(|port| port.parse::<u16>()) {
+        Some(Ok(0)) | Some(Err(_)) => Err(String::from("the port has to be between 1 and 65535")),
+        _ => Ok(()),
+    }
+}
+
+/// Checks a jdbc url. The urls of the databases MagicINFO supports are checked
+/// in more detail.
+fn check_jdbc_url(value: &str) -> Result<(), String> {
+    let captures = JDBC_REGEX.captures(value).ok_or_else(|| String::from("expected a jdbc url like jdbc:postgresql://host:port/database"))?;
+    let expected = match captures.get(1).unwrap().as_str() {
+        "postgresql" => "expected jdbc:postgresql://host[:port]/database",
+        "sqlserver" => "expected jdbc:sqlserver://host[\\instance][:port];databaseName=database",
+        _ => return Ok(()),
+    };
+
+    match jdbc::parse(value) {
+        Ok(url) if url.database.is_some() => Ok(()),
+        _ => Err(String::from(expected)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schema;
+    use crate::config::syntax::parse;
+
+    #[test]
+    fn test_if_values_are_checked_against_their_type() {
+        let schema = Schema::builtin();
+        assert!(schema.check_value("listen.port", "7001").is_ok());
+        assert!(schema.check_value("listen.port", "70o1").is_err());
+        assert!(schema.check_value("listen.port", "70001").is_err());
+        assert!(schema.check_value("device.log4j.level", "debug").is_ok());
+        assert!(schema.check_value("device.log4j.level", "VERBOSE").is_err());
+        assert!(schema.check_value("web_url", "https://db01:7002/MagicInfo").is_ok());
+        assert!(schema.check_value("web_url", "db01:7002/MagicInfo").is_err());
+        assert!(schema.check_value("wsrm.url", "jdbc:postgresql://localhost:5432/magicinfo").is_ok());
+        assert!(schema.check_value("wsrm.url", "jdbc:sqlserver://db01\\SQLEXPRESS:1433;databaseName=magicinfo").is_ok());
+        assert!(schema.check_value("wsrm.url", "jdbc:postgresql://localhost:5432").is_err());
+        assert!(schema.check_value("CONTENTS_HOME", "C:/MagicInfo Premium/runtime/upload").is_ok());
+        assert!(schema.check_value("CONTENTS_HOME", "").is_err());
+        assert!(schema.check_value("unknown.key", "anything").is_ok());
+    }
+
+    #[test]
+    fn test_if_secret_values_are_not_shown() {
+        use super::KeyRule;
+
+        let mut schema = Schema::builtin();
+        let rule = KeyRule { pattern: Some(String::from("[A-Z]+")), ..KeyRule::default() };
+        schema.rules.insert(String::from("wsrm.password"), rule);
+
+        let message = schema.check_value("wsrm.password", "hunter2").unwrap_err();
+        assert!(!message.contains("hunter2"));
+        assert!(schema.check_value("listen.port", "70o1").unwrap_err().contains("70o1"));
+    }
+
+    #[test]
+    fn test_if_missing_required_keys_are_found() {
+        let schema = Schema::builtin();
+        let problems = schema.validate(&parse("listen.port=0\nwsrm.dbVendor=PostgreSQL\n"));
+        let found: Vec<(Option<usize>, &str)> = problems.iter().map(|problem| (problem.line, problem.kind)).collect();
+
+        assert_eq!(found[0], (Some(1), "invalid-value"));
+        assert_eq!(found.len(), 1 + schema.required_keys().count() - 1);
+    }
+}
@@ -0,0 +1,422 @@
+use std::fmt::Write;
+
+/// One logical line from a properties file. A logical line spans multiple
+/// physical lines when those end with a line continuation backslash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalLine {
+    /// The 1-based number of the first physical line.
+    pub line_number: usize,
+
+    /// The original text of all physical lines, without the line terminator of
+    /// the last physical line.
+    pub raw: String,
+
+    /// The line terminator of the last physical line. Empty if the line is the
+    /// last line of a file that doesn't end with a line terminator.
+    pub ending: String,
+
+    pub kind: LineKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineKind {
+    Blank,
+    Comment,
+    Property {
+        key: String,
+        value: String,
+
+        /// Byte offsets in `raw` where the key ends and the value starts. The
+        /// text in between is the separator.
+        key_end: usize,
+        value_start: usize,
+    },
+    Invalid { reason: String },
+}
+
+impl LogicalLine {
+    /// Returns the unescaped key when this line contains a property.
+    pub fn key(&self) -> Option<&str> {
+        match &self.kind {
+            LineKind::Property { key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Returns the line with its value replaced, keeping the indentation, the
+    /// key and the separator exactly as they were. The given separator is used
+    /// if the line didn't have one.
+    pub fn with_value(&self, value: &str, default_separator: &str) -> String {
+        let LineKind::Property { key_end, value_start, .. } = self.kind else {
+            return self.raw.clone();
+        };
+
+        let mut line = String::from(&self.raw[..value_start]);
+        if key_end == value_start {
+            line.push_str(default_separator);
+        }
+        line.push_str(&escape_value(value));
+        line
+    }
+
+    /// Checks if a property line separates its key and value with '=' or ':',
+    /// instead of only with whitespace or not at all.
+    pub fn has_explicit_separator(&self) -> bool {
+        let LineKind::Property { key_end, value_start, .. } = self.kind else {
+            return false;
+        };
+
+        self.raw[key_end..value_start].contains(['=', ':'])
+    }
+
+    /// Returns the separator of a property line, when it's written on a
+    /// single physical line.
+    pub fn separator(&self) -> Option<&str> {
+        let LineKind::Property { key_end, value_start, .. } = self.kind else {
+            return None;
+        };
+
+        let separator = &self.raw[key_end..value_start];
+        if separator.is_empty() || separator.contains(['\r', '\n']) {
+            return None;
+        }
+        Some(separator)
+    }
+}
+
+/// Parses the content of a properties file into logical lines, following the
+/// rules of Java's `Properties.load`: `=`, `:` and whitespace separators,
+/// backslash line continuations, `\uXXXX` escapes and escaped characters in
+/// keys and values.
+pub fn parse(content: &str) -> Vec<LogicalLine> {
+    let physical_lines = split_physical_lines(content);
+    let mut lines = Vec::new();
+    let mut index = 0;
+
+    while index < physical_lines.len() {
+        let first_index = index;
+        let (text, _) = physical_lines[index];
+        let start = offset_in(content, text);
+        let trimmed = trim_start_whitespace(text);
+
+        let kind = if trimmed.is_empty() {
+            LineKind::Blank
+        } else if trimmed.starts_with('#') || trimmed.starts_with('!') {
+            LineKind::Comment
+        } else {
+            // Every character of the logical line is stored together with its
+            // byte offset in the raw text of the physical lines.
+            let mut logical: Vec<(char, usize)> = Vec::new();
+            let mut current = trimmed;
+            loop {
+                let continues = ends_with_continuation(current);
+                let current_start = offset_in(content, current) - start;
+                let current_end = if continues { current.len() - 1 } else { current.len() };
+                logical.extend(current[..current_end].char_indices().map(|(i, c)| (c, current_start + i)));
+
+                if !continues || index + 1 >= physical_lines.len() {
+                    break;
+                }
+
+                // An empty continuation line terminates the logical line.
+                index += 1;
+                current = trim_start_whitespace(physical_lines[index].0);
+                if current.is_empty() {
+                    break;
+                }
+            }
+
+            let (text, _) = physical_lines[index];
+            let raw_length = offset_in(content, text) + text.len() - start;
+            parse_key_value(&logical, raw_length)
+        };
+
+        let (last_text, ending) = physical_lines[index];
+        let end = offset_in(content, last_text) + last_text.len();
+        lines.push(LogicalLine {
+            line_number: first_index + 1,
+            raw: String::from(&content[start..end]),
+            ending: String::from(ending),
+            kind,
+        });
+        index += 1;
+    }
+
+    lines
+}
+
+/// Escapes a key so it can be written to a properties file.
+pub fn escape_key(key: &str) -> String {
+    escape(key, true)
+}
+
+/// Escapes a value so it can be written to a properties file.
+pub fn escape_value(value: &str) -> String {
+    escape(value, false)
+}
+
+/// Formats a key and value as a single properties line, without line ending.
+pub fn format_property(key: &str, separator: &str, value: &str) -> String {
+    format!("{}{}{}", escape_key(key), separator, escape_value(value))
+}
+
+/// Splits the key from the value of a logical line and unescapes both.
+fn parse_key_value(logical: &[(char, usize)], raw_length: usize) -> LineKind {
+    let chars: Vec<char> = logical.iter().map(|(c, _)| *c).collect();
+    let mut key_end = 0;
+    let mut value_start = chars.len();
+    let mut has_separator = false;
+    let mut preceding_backslash = false;
+
+    // The key ends at the first unescaped '=', ':' or whitespace character.
+    while key_end < chars.len() {
+        let c = chars[key_end];
+        if !preceding_backslash && (c == '=' || c == ':') {
+            value_start = key_end + 1;
+            has_separator = true;
+            break;
+        }
+        if !preceding_backslash && is_whitespace(c) {
+            value_start = key_end + 1;
+            break;
+        }
+
+        preceding_backslash = c == '\\' && !preceding_backslash;
+        key_end += 1;
+    }
+
+    // Whitespace around the separator is skipped, as is one '=' or ':' after
+    // a whitespace separator.
+    while value_start < chars.len() {
+        let c = chars[value_start];
+        if !is_whitespace(c) {
+            if has_separator || (c != '=' && c != ':') {
+                break;
+            }
+            has_separator = true;
+        }
+        value_start += 1;
+    }
+
+    let raw_offset = |index: usize| logical.get(index).map(|(_, offset)| *offset).unwrap_or(raw_length);
+    let key_res = unescape(&chars[..key_end]);
+    let value_res = unescape(&chars[value_start..]);
+    match (key_res, value_res) {
+        (Ok(key), Ok(value)) => LineKind::Property {
+            key,
+            value,
+            key_end: raw_offset(key_end),
+            value_start: raw_offset(value_start),
+        },
+        (Err(reason), _) | (_, Err(reason)) => LineKind::Invalid { reason },
+    }
+}
+
+/// Converts escape sequences to the characters they represent.
+fn unescape(chars: &[char]) -> Result<String, String> {
+    let mut output = String::with_capacity(chars.len());
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        index += 1;
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        let Some(&escaped) = chars.get(index) else {
+            break;
+        };
+        index += 1;
+
+        match escaped {
+            't' => output.push('\t'),
+            'r' => output.push('\r'),
+            'n' => output.push('\n'),
+            'f' => output.push('\x0c'),
+            'u' => {
+                let hex: String = chars.iter().skip(index).take(4).collect();
+                let code_unit = if hex.len() == 4 { u16::from_str_radix(&hex, 16).ok() } else { None };
+                let Some(code_unit) = code_unit else {
+                    return Err(String::from("Malformed \\uxxxx encoding"));
+                };
+                index += 4;
+                push_utf16(&mut output, code_unit, chars, &mut index);
+            },
+            other => output.push(other),
+        }
+    }
+
+    Ok(output)
+}
+
+/// Pushes an UTF-16 code unit onto the output. A high surrogate is combined
+/// with a directly following `\uXXXX` low surrogate.
+fn push_utf16(output: &mut String, code_unit: u16, chars: &[char], index: &mut usize) {
+    if (0xD800..0xDC00).contains(&code_unit) && chars.get(*index) == Some(&'\\') && chars.get(*index + 1) == Some(&'u') {
+        let hex: String = chars.iter().skip(*index + 2).take(4).collect();
+        if let Ok(low) = u16::from_str_radix(&hex, 16) {
+            if hex.len() == 4 && (0xDC00..0xE000).contains(&low) {
+                *index += 6;
+                output.extend(char::decode_utf16([code_unit, low]).map(|c| c.unwrap_or('\u{FFFD}')));
+                return;
+            }
+        }
+    }
+
+    output.extend(char::decode_utf16([code_unit]).map(|c| c.unwrap_or('\u{FFFD}')));
+}
+
+/// Escapes text the way Java's `Properties.store` does. Characters outside of
+/// printable ASCII are written as `\uXXXX`, as MagicINFO reads its properties
+/// files as ISO-8859-1.
+fn escape(text: &str, is_key: bool) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\x0c' => output.push_str("\\f"),
+            ' ' if is_key || index == 0 => output.push_str("\\ "),
+            '=' | ':' if is_key => {
+                output.push('\\');
+                output.push(c);
+            },
+            '#' | '!' if is_key && index == 0 => {
+                output.push('\\');
+                output.push(c);
+            },
+            ' '..='~' => output.push(c),
+            _ => {
+                let mut buffer = [0u16; 2];
+                for code_unit in c.encode_utf16(&mut buffer) {
+                    let _ = write!(output, "\\u{code_unit:04X}");
+                }
+            },
+        }
+    }
+
+    output
+}
+
+/// Splits the content into physical lines and their line terminators. Lines
+/// can be terminated by "\n", "\r" or "\r\n".
+fn split_physical_lines(content: &str) -> Vec<(&str, &str)> {
+    let mut lines = Vec::new();
+    let bytes = content.as_bytes();
+    let mut start = 0;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\n' => {
+                lines.push((&content[start..index], &content[index..index + 1]));
+                start = index + 1;
+            },
+            b'\r' => {
+                let ending_end = if bytes.get(index + 1) == Some(&b'\n') { index + 2 } else { index + 1 };
+                lines.push((&content[start..index], &content[index..ending_end]));
+                index = ending_end - 1;
+                start = ending_end;
+            },
+            _ => {},
+        }
+        index += 1;
+    }
+
+    if start < bytes.len() {
+        lines.push((&content[start..], ""));
+    }
+
+    lines
+}
+
+/// Returns the byte offset of a slice within the content it was taken from.
+fn offset_in(content: &str, slice: &str) -> usize {
+    slice.as_ptr() as usize - content.as_ptr() as usize
+}
+
+/// Checks if a line ends with an odd number of backslashes, which means the
+/// logical line continues on the next physical line.
+fn ends_with_continuation(line: &str) -> bool {
+    let backslashes = line.chars().rev().take_while(|c| *c == '\\').count();
+    backslashes % 2 == 1
+}
+
+fn trim_start_whitespace(line: &str) -> &str {
+    line.trim_start_matches(is_whitespace)
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\x0c'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, escape_key, escape_value, LineKind};
+
+    fn properties(content: &str) -> Vec<(String, String)> {
+        parse(content).into_iter().filter_map(|line| match line.kind {
+            LineKind::Property { key, value, .. } => Some((key, value)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_if_all_separators_are_recognized() {
+        let parsed = properties("a=1\nb : 2\nc 3\n  d\t=  4\ne\nf:\n");
+        assert_eq!(parsed, vec![
+            (String::from("a"), String::from("1")),
+            (String::from("b"), String::from("2")),
+            (String::from("c"), String::from("3")),
+            (String::from("d"), String::from("4")),
+            (String::from("e"), String::new()),
+            (String::from("f"), String::new()),
+        ]);
+    }
+
+    #[test]
+    fn test_if_line_continuations_are_joined() {
+        let lines = parse("list = one, \\\r\n      two, \\\r\n      three\r\n# done\r\n");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].raw, "list = one, \\\r\n      two, \\\r\n      three");
+        assert_eq!(lines[0].ending, "\r\n");
+        assert_eq!(lines[0].key(), Some("list"));
+        assert!(matches!(&lines[0].kind, LineKind::Property { value, .. } if value == "one, two, three"));
+        assert_eq!(lines[1].line_number, 4);
+        assert_eq!(lines[1].kind, LineKind::Comment);
+    }
+
+    #[test]
+    fn test_if_escapes_are_resolved() {
+        let parsed = properties("path\\ name\\=x = C:\\\\MagicInfo Premium\\\\conf\nunicode = caf\\u00E9\\t!\n");
+        assert_eq!(parsed[0], (String::from("path name=x"), String::from("C:\\MagicInfo Premium\\conf")));
+        assert_eq!(parsed[1], (String::from("unicode"), String::from("caf\u{e9}\t!")));
+    }
+
+    #[test]
+    fn test_if_malformed_unicode_escapes_are_invalid() {
+        let lines = parse("broken = \\u12G4\n");
+        assert!(matches!(lines[0].kind, LineKind::Invalid { .. }));
+    }
+
+    #[test]
+    fn test_if_escaped_text_parses_back() {
+        let key = "#weird key:=";
+        let value = " C:\\MagicInfo Premium\\caf\u{e9}\n";
+        let line = format!("{}={}", escape_key(key), escape_value(value));
+        assert_eq!(properties(&line), vec![(String::from(key), String::from(value))]);
+    }
+
+    #[test]
+    fn test_if_values_are_replaced_in_place() {
+        let lines = parse("  listen.port\t:  7001\nflag\n");
+        assert_eq!(lines[0].separator(), Some("\t:  "));
+        assert_eq!(lines[0].with_value("7002", " = "), "  listen.port\t:  7002");
+        assert_eq!(lines[1].with_value("true", " = "), "flag = true");
+    }
+}
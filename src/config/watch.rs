@@ -0,0 +1,192 @@
+use std::{collections::HashMap, fs, path::Path, thread, time::{Duration, SystemTime}};
+use chrono::Local;
+
+use super::diff::diff;
+use super::properties::PropertiesMut;
+use crate::utils::print_error;
+
+/// A change to a single key of a watched properties file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangeEvent {
+    pub timestamp: String,
+    pub kind: &'static str,
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<String>,
+}
+
+impl ChangeEvent {
+    /// Formats the event as a single line, like `config diff` does.
+    pub fn format_text(&self) -> String {
+        let old = self.old.as_deref().unwrap_or_default();
+        let new = self.new.as_deref().unwrap_or_default();
+        match self.kind {
+            "added" => format!("{} + {} = {new}", self.timestamp, self.key),
+            "removed" => format!("{} - {} = {old}", self.timestamp, self.key),
+            _ => format!("{} ~ {} = {old} -> {new}", self.timestamp, self.key),
+        }
+    }
+}
+
+/// Returns an event for every key that was added, removed or changed, in
+/// alphabetical order.
+pub fn get_change_events(old: &HashMap<String, String>, new: &HashMap<String, String>, timestamp: &str) -> Vec<ChangeEvent> {
+    let properties_diff = diff(old, new);
+    let event = |kind: &'static str, key: &String, old: Option<&String>, new: Option<&String>| ChangeEvent {
+        timestamp: String::from(timestamp),
+        kind,
+        key: key.clone(),
+        old: old.cloned(),
+        new: new.cloned(),
+    };
+
+    let mut events: Vec<ChangeEvent> = properties_diff.added.iter().map(|(key, value)| event("added", key, None, Some(value)))
+        .chain(properties_diff.removed.iter().map(|(key, value)| event("removed", key, Some(value), None)))
+        .chain(properties_diff.changed.iter().map(|(key, change)| event("changed", key, Some(&change.old), Some(&change.new))))
+        .collect();
+    events.sort_by(|a, b| a.key.cmp(&b.key));
+    events
+}
+
+/// Polls the properties file for modifications and calls on_events with the
+/// key-level changes every time its content changed. Runs until the process
+/// is stopped. A file that can't be read, like while it's being replaced, is
+/// tried again at the next poll. Changes are only reported once the file has
+/// been read for the first time, and once the file stayed the same for a
+/// whole interval, so a file that is still being written isn't read halfway.
+pub fn watch(path: &Path, interval: Duration, mut on_events: impl FnMut(Vec<ChangeEvent>)) {
+    let mut last_error = None;
+    let (mut last_modification, mut values) = loop {
+        let modification = get_modification(path);
+        if let Some(values) = read_values(path, &mut last_error) {
+            break (modification, values);
+        }
+        thread::sleep(interval);
+    };
+
+    let mut pending_modification = None;
+    loop {
+        thread::sleep(interval);
+
+        let modification = get_modification(path);
+        if modification == last_modification {
+            pending_modification = None;
+            continue;
+        }
+        if modification.is_some() && modification != pending_modification {
+            pending_modification = modification;
+            continue;
+        }
+
+        let Some(new_values) = read_values(path, &mut last_error) else {
+            continue;
+        };
+
+        last_modification = modification;
+        let timestamp = Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+        let events = get_change_events(&values, &new_values, &timestamp);
+        values = new_values;
+
+        if !events.is_empty() {
+            on_events(events);
+        }
+    }
+}
+
+/// The modification time, size and content hash of a file. The hash catches
+/// a rewrite of the same length within the precision of the modification time,
+/// like changing a single digit of a port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Modification {
+    modified: SystemTime,
+    len: u64,
+    digest: md5::Digest,
+}
+
+/// Returns the modification of the file, which changes when it's written.
+fn get_modification(path: &Path) -> Option<Modification> {
+    let metadata = fs::metadata(path).ok()?;
+    let content = fs::read(path).ok()?;
+    Some(Modification {
+        modified: metadata.modified().ok()?,
+        len: metadata.len(),
+        digest: md5::compute(content),
+    })
+}
+
+/// Reads the values of the file. An error is only printed if it differs from
+/// the last one, so a file that can't be read doesn't fill the output.
+fn read_values(path: &Path, last_error: &mut Option<String>) -> Option<HashMap<String, String>> {
+    let values_res = PropertiesMut::open(path.to_str().unwrap()).and_then(|properties| properties.get_hashmap_content());
+    match values_res {
+        Ok(values) => {
+            *last_error = None;
+            Some(values)
+        },
+        Err(e) => {
+            let error_message = format!("{}: {e}", path.display());
+            if last_error.as_ref() != Some(&error_message) {
+                print_error(&error_message);
+                *last_error = Some(error_message);
+            }
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs};
+    use super::{get_change_events, get_modification, read_values};
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(key, value)| (String::from(*key), String::from(*value))).collect()
+    }
+
+    #[test]
+    fn test_if_changes_become_events() {
+        let old = map(&[("listen.port", "7001"), ("web_url", "https://db01:7002/MagicInfo")]);
+        let new = map(&[("listen.port", "7002"), ("device.log4j.level", "DEBUG")]);
+
+        let lines: Vec<String> = get_change_events(&old, &new, "t").iter().map(|event| event.format_text()).collect();
+        assert_eq!(lines, vec![
+            "t + device.log4j.level = DEBUG",
+            "t ~ listen.port = 7001 -> 7002",
+            "t - web_url = https://db01:7002/MagicInfo",
+        ]);
+    }
+
+    #[test]
+    fn test_if_read_errors_are_remembered() {
+        let path = env::temp_dir().join(format!("magicutil-watch-{}.properties", std::process::id()));
+        let mut last_error = None;
+
+        assert!(read_values(&path, &mut last_error).is_none());
+        let first_error = last_error.clone();
+        assert!(read_values(&path, &mut last_error).is_none());
+        assert_eq!(last_error, first_error);
+
+        fs::write(&path, "listen.port=7001\n").unwrap();
+        let values = read_values(&path, &mut last_error);
+        fs::remove_file(&path).unwrap();
+
+        assert!(first_error.is_some());
+        assert_eq!(values.unwrap()["listen.port"], "7001");
+        assert!(last_error.is_none());
+    }
+
+    #[test]
+    fn test_if_same_length_edits_are_noticed() {
+        let path = env::temp_dir().join(format!("magicutil-watch-edit-{}.properties", std::process::id()));
+        fs::write(&path, "listen.port=7001\n").unwrap();
+        let first = get_modification(&path);
+        fs::write(&path, "listen.port=7002\n").unwrap();
+        let second = get_modification(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(first.is_some());
+        assert_ne!(first, second);
+    }
+}
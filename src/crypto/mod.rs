@@ -0,0 +1,94 @@
+use std::{fs, io::Read, path::Path, process::exit};
+
+use clap::ArgMatches;
+use simple_error::SimpleError;
+
+use crate::config::{self, Ciphers};
+use crate::utils::print_error;
+
+/// Handles all crypto related commands.
+pub fn handle_crypto_command(submatches: &ArgMatches) {
+    match submatches.subcommand() {
+        Some(("encrypt", subsubmatches)) => transform_values(subsubmatches, Ciphers::encrypt, "encrypt"),
+        Some(("decrypt", subsubmatches)) => transform_values(subsubmatches, Ciphers::decrypt, "decrypt"),
+        _ => {
+            unreachable!("No valid subcommand found")
+        }
+    }
+}
+
+/// Encrypts or decrypts the values given as arguments, or the lines of stdin
+/// if no values are given, and prints one result per line. Nothing is printed
+/// if one of the values fails.
+fn transform_values(submatches: &ArgMatches, transform: fn(&Ciphers, &str) -> Result<String, SimpleError>, action: &str) {
+    let cipher_name = submatches.get_one::<String>("cipher").unwrap();
+    let ciphers_res = get_key(submatches).and_then(|key| Ciphers::new(&key, cipher_name));
+    if let Err(e) = ciphers_res {
+        print_error(e);
+        exit(1);
+    }
+
+    let ciphers = ciphers_res.unwrap();
+    let values = get_values(submatches);
+
+    let mut results = Vec::with_capacity(values.len());
+    let mut failed = false;
+    for (index, value) in values.iter().enumerate() {
+        match transform(&ciphers, value) {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                let error_message = format!("Could not {action} value {}: {e}", index + 1);
+                print_error(error_message);
+                failed = true;
+            },
+        }
+    }
+
+    if failed {
+        exit(1);
+    }
+
+    for result in results {
+        println!("{result}");
+    }
+}
+
+/// Returns the encryption key from a file containing only the key, from a
+/// properties file, or from --key or MAGICUTIL_ENCRYPTION_KEY, in that order.
+fn get_key(submatches: &ArgMatches) -> Result<String, SimpleError> {
+    let key = if let Some(key_file) = submatches.get_one::<String>("key-file") {
+        let content = fs::read_to_string(key_file).map_err(|e| {
+            let error_message = format!("Could not read the key file {key_file}: {e}");
+            SimpleError::new(error_message)
+        })?;
+        String::from(content.trim())
+    } else if let Some(properties_file) = submatches.get_one::<String>("properties") {
+        config::read_encryption_key(Path::new(properties_file))?
+    } else if let Some(key) = submatches.get_one::<String>("key") {
+        key.clone()
+    } else {
+        return Err(SimpleError::new("Expected an encryption key from --key, --key-file, --properties or MAGICUTIL_ENCRYPTION_KEY"));
+    };
+
+    if key.is_empty() {
+        return Err(SimpleError::new("Expected a non-empty encryption key"));
+    }
+
+    Ok(key)
+}
+
+/// Returns the values given as arguments, or the lines of stdin.
+fn get_values(submatches: &ArgMatches) -> Vec<String> {
+    if let Some(values) = submatches.get_many::<String>("VALUE") {
+        return values.cloned().collect();
+    }
+
+    let mut content = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut content) {
+        let error_message = format!("Could not read the values from stdin: {e}");
+        print_error(error_message);
+        exit(1);
+    }
+
+    content.lines().map(String::from).collect()
+}
@@ -0,0 +1,260 @@
+mod output;
+
+use std::{fs, io::Read, process::exit, time::Duration};
+
+use clap::ArgMatches;
+use native_tls::{Certificate, TlsConnector};
+use postgres::{config::SslMode, Client, NoTls, SimpleQueryMessage};
+use postgres_native_tls::MakeTlsConnector;
+use simple_error::SimpleError;
+
+use crate::config::{get_decrypted_config_properties, parse_jdbc_url, JdbcUrl, POSTGRESQL_VENDOR};
+use crate::utils::print_error;
+
+use self::output::ResultSet;
+
+const DATABASE_PROPERTIES: &[&str] = &["wsrm.url", "wsrm.username", "wsrm.password"];
+
+/// Handles all database related commands.
+pub fn handle_db_command(submatches: &ArgMatches) {
+    match submatches.subcommand() {
+        Some(("query", subsubmatches)) => run_query(subsubmatches),
+        _ => {
+            unreachable!("No valid subcommand found")
+        }
+    }
+}
+
+/// Runs the SQL from the argument or file on MagicINFO's database and prints
+/// the rows of every statement that returns rows.
+fn run_query(submatches: &ArgMatches) {
+    let sql = get_sql(submatches);
+    if sql.trim().is_empty() {
+        print_error("Expected a SQL query, or a file with --file");
+        exit(1);
+    }
+
+    let timeout = Duration::from_secs(*submatches.get_one::<u64>("timeout").unwrap());
+    let result_sets_res = connect(timeout).and_then(|mut client| {
+        client.simple_query(&sql).map_err(|e| {
+            let error_message = format!("The query failed: {}", describe_error(&e));
+            SimpleError::new(error_message)
+        })
+    });
+
+    if let Err(e) = result_sets_res {
+        print_error(e);
+        exit(1);
+    }
+
+    let result_sets = collect_result_sets(result_sets_res.unwrap());
+    let format = submatches.get_one::<String>("format").map(|format| format.as_str());
+    for (index, result_set) in result_sets.iter().enumerate() {
+        match format {
+            Some("csv") => print!("{}", output::format_csv(result_set)),
+            Some("json") => println!("{}", output::format_json(result_set)),
+            _ => {
+                if index > 0 {
+                    println!();
+                }
+                print!("{}", output::format_table(result_set));
+            },
+        }
+    }
+}
+
+/// Returns the SQL given as argument, or read from the file given with --file.
+/// The file `-` is read from stdin.
+fn get_sql(submatches: &ArgMatches) -> String {
+    if let Some(sql) = submatches.get_one::<String>("SQL") {
+        return sql.clone();
+    }
+
+    let Some(file) = submatches.get_one::<String>("file") else {
+        return String::new();
+    };
+
+    let sql_res = if file == "-" {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content).map(|_| content)
+    } else {
+        fs::read_to_string(file)
+    };
+
+    sql_res.unwrap_or_else(|e| {
+        let error_message = format!("Could not read the SQL from {file}: {e}");
+        print_error(error_message);
+        exit(1);
+    })
+}
+
+/// Connects to the database configured in the config.properties file, with
+/// the decrypted username and password.
+fn connect(timeout: Duration) -> Result<Client, SimpleError> {
+    let properties = get_decrypted_config_properties(DATABASE_PROPERTIES)?;
+    let property = |key: &str| properties.get(key).cloned().flatten().ok_or_else(|| {
+        let error_message = format!("'{key}' is not set in the config.properties file");
+        SimpleError::new(error_message)
+    });
+
+    let url = parse_jdbc_url(&property("wsrm.url")?)?;
+    connect_to(&url, &property("wsrm.username")?, &property("wsrm.password")?, timeout)
+}
+
+/// Connects to the database at the url with the given credentials.
+fn connect_to(url: &JdbcUrl, user: &str, password: &str, timeout: Duration) -> Result<Client, SimpleError> {
+    if url.vendor != POSTGRESQL_VENDOR {
+        let error_message = format!("Querying {} databases is not supported, only PostgreSQL", url.vendor);
+        return Err(SimpleError::new(error_message));
+    }
+
+    let mut config = postgres::Config::new();
+    config
+        .host(&url.host)
+        .port(url.port)
+        .user(user)
+        .password(password)
+        .connect_timeout(timeout)
+        .application_name("magicutil");
+    if let Some(database) = &url.database {
+        config.dbname(database);
+    }
+
+    let connect_res = match get_tls_connector(url)? {
+        Some(connector) => {
+            config.ssl_mode(SslMode::Require);
+            config.connect(MakeTlsConnector::new(connector))
+        },
+        None => config.connect(NoTls),
+    };
+
+    connect_res.map_err(|e| {
+        let error_message = format!("Could not connect to the database at {}:{}: {}", url.host, url.port, describe_error(&e));
+        SimpleError::new(error_message)
+    })
+}
+
+/// Returns a TLS connector if the url requires SSL, like the JDBC driver reads
+/// its parameters. `ssl=true` and `sslmode=verify-full` verify the certificate
+/// and host name, `verify-ca` only the certificate and `require` neither. A
+/// root certificate can be given with `sslrootcert`.
+fn get_tls_connector(url: &JdbcUrl) -> Result<Option<TlsConnector>, SimpleError> {
+    let ssl_mode = match url.parameters.get("sslmode").map(|mode| mode.as_str()) {
+        Some(mode) => mode,
+        None if url.parameters.get("ssl").is_some_and(|ssl| ssl == "true") => "verify-full",
+        None => return Ok(None),
+    };
+
+    let mut builder = TlsConnector::builder();
+    match ssl_mode {
+        "disable" | "allow" | "prefer" => return Ok(None),
+        "require" => {
+            builder.danger_accept_invalid_certs(true);
+        },
+        "verify-ca" => {
+            builder.danger_accept_invalid_hostnames(true);
+        },
+        "verify-full" => {},
+        _ => return Err(SimpleError::new(format!("Unknown sslmode '{ssl_mode}' in the database url"))),
+    }
+
+    if let Some(root_cert_path) = url.parameters.get("sslrootcert") {
+        let root_cert = fs::read(root_cert_path).map_err(|e| {
+            let error_message = format!("Could not read the root certificate {root_cert_path}: {e}");
+            SimpleError::new(error_message)
+        })?;
+        let certificate = Certificate::from_pem(&root_cert).map_err(|e| {
+            let error_message = format!("Could not parse the root certificate {root_cert_path}: {e}");
+            SimpleError::new(error_message)
+        })?;
+        builder.add_root_certificate(certificate);
+    }
+
+    builder.build().map(Some).map_err(|e| {
+        let error_message = format!("Could not set up SSL: {e}");
+        SimpleError::new(error_message)
+    })
+}
+
+/// Groups the rows per statement. Statements that don't return rows, like
+/// updates, are returned as a result set with only the number of rows.
+fn collect_result_sets(messages: Vec<SimpleQueryMessage>) -> Vec<ResultSet> {
+    let mut result_sets = Vec::new();
+    let mut current = ResultSet::default();
+
+    for message in messages {
+        match message {
+            // The columns are described before the rows, so they're known even
+            // if the statement returns no rows.
+            SimpleQueryMessage::RowDescription(columns) => {
+                current.columns = columns.iter().map(|column| String::from(column.name())).collect();
+            },
+            SimpleQueryMessage::Row(row) => {
+                current.rows.push((0..row.len()).map(|index| row.get(index).map(String::from)).collect());
+            },
+            SimpleQueryMessage::CommandComplete(count) => {
+                current.count = count;
+                result_sets.push(std::mem::take(&mut current));
+            },
+            _ => {},
+        }
+    }
+
+    result_sets
+}
+
+/// Returns the message of a database error, which is more useful than the
+/// generic description of the error kind.
+fn describe_error(e: &postgres::Error) -> String {
+    match e.as_db_error() {
+        Some(db_error) => db_error.message().to_string(),
+        None => match std::error::Error::source(e) {
+            Some(source) => format!("{e}: {source}"),
+            None => e.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, time::Duration};
+    use super::{collect_result_sets, connect_to, get_tls_connector};
+    use crate::config::parse_jdbc_url;
+
+    /// Runs against a local PostgreSQL, with `cargo test -- --ignored`. The
+    /// database is read from MAGICUTIL_TEST_DB_URL, MAGICUTIL_TEST_DB_USER and
+    /// MAGICUTIL_TEST_DB_PASSWORD.
+    #[test]
+    #[ignore]
+    fn test_if_queries_run_on_a_local_database() {
+        let setting = |name: &str, default: &str| env::var(name).unwrap_or_else(|_| String::from(default));
+        let url = parse_jdbc_url(&setting("MAGICUTIL_TEST_DB_URL", "jdbc:postgresql://127.0.0.1:5432/postgres")).unwrap();
+        let user = setting("MAGICUTIL_TEST_DB_USER", "postgres");
+        let password = setting("MAGICUTIL_TEST_DB_PASSWORD", "postgres");
+
+        let mut client = connect_to(&url, &user, &password, Duration::from_secs(5)).unwrap();
+        let messages = client.simple_query("select 1 as id, null as name where false; select 2 as id, 'lobby' as name; create temporary table t (id int)").unwrap();
+        let result_sets = collect_result_sets(messages);
+
+        assert_eq!(result_sets.len(), 3);
+        assert_eq!(result_sets[0].columns, vec!["id", "name"]);
+        assert!(result_sets[0].rows.is_empty());
+        assert_eq!(result_sets[1].rows, vec![vec![Some(String::from("2")), Some(String::from("lobby"))]]);
+        assert_eq!(result_sets[1].count, 1);
+        assert!(result_sets[2].columns.is_empty());
+
+        assert!(connect_to(&url, &user, "wrong password", Duration::from_secs(5)).is_err());
+    }
+
+    #[test]
+    fn test_if_ssl_is_read_from_the_url() {
+        let connector = |url: &str| get_tls_connector(&parse_jdbc_url(url).unwrap()).map(|connector| connector.is_some());
+
+        assert!(!connector("jdbc:postgresql://localhost:5432/magicinfo").unwrap());
+        assert!(!connector("jdbc:postgresql://localhost:5432/magicinfo?sslmode=prefer").unwrap());
+        assert!(connector("jdbc:postgresql://localhost:5432/magicinfo?ssl=true").unwrap());
+        assert!(connector("jdbc:postgresql://localhost:5432/magicinfo?sslmode=require").unwrap());
+        assert!(connector("jdbc:postgresql://localhost:5432/magicinfo?sslmode=always").is_err());
+        assert!(connector("jdbc:postgresql://localhost:5432/magicinfo?sslmode=verify-ca&sslrootcert=/nonexistent.crt").is_err());
+    }
+}
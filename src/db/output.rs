@@ -0,0 +1,135 @@
+use pad::PadStr;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// The rows returned by a single statement. All values are text, as sent by
+/// the database, or None for NULL.
+#[derive(Debug, Default, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+
+    /// The number of rows returned or changed by the statement.
+    pub count: u64,
+}
+
+/// Formats the rows as a table with aligned columns, followed by the number of
+/// rows.
+pub fn format_table(result_set: &ResultSet) -> String {
+    if result_set.columns.is_empty() {
+        return format_count(result_set.count);
+    }
+
+    let widths: Vec<usize> = result_set.columns.iter().enumerate().map(|(index, column)| {
+        result_set.rows.iter()
+            .map(|row| row[index].as_deref().unwrap_or_default().chars().count())
+            .chain([column.chars().count()])
+            .max()
+            .unwrap()
+    }).collect();
+
+    let format_row = |values: Vec<&str>| -> String {
+        let cells: Vec<String> = values.iter().zip(&widths).map(|(value, width)| value.pad_to_width(*width)).collect();
+        format!("{}\n", cells.join(" | ").trim_end())
+    };
+
+    let mut output = format_row(result_set.columns.iter().map(|column| column.as_str()).collect());
+    let separators: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    output.push_str(&format!("{}\n", separators.join("-+-")));
+    for row in &result_set.rows {
+        output.push_str(&format_row(row.iter().map(|value| value.as_deref().unwrap_or_default()).collect()));
+    }
+    output.push_str(&format_count(result_set.rows.len() as u64));
+
+    output
+}
+
+fn format_count(count: u64) -> String {
+    match count {
+        1 => String::from("(1 row)\n"),
+        _ => format!("({count} rows)\n"),
+    }
+}
+
+/// Formats the rows as CSV with a header line. NULL values are empty.
+pub fn format_csv(result_set: &ResultSet) -> String {
+    if result_set.columns.is_empty() {
+        return String::new();
+    }
+
+    let format_row = |values: Vec<&str>| -> String {
+        let cells: Vec<String> = values.iter().map(|value| quote_csv_value(value)).collect();
+        format!("{}\r\n", cells.join(","))
+    };
+
+    let mut output = format_row(result_set.columns.iter().map(|column| column.as_str()).collect());
+    for row in &result_set.rows {
+        output.push_str(&format_row(row.iter().map(|value| value.as_deref().unwrap_or_default()).collect()));
+    }
+
+    output
+}
+
+/// Formats the rows as a json array with an object per row.
+pub fn format_json(result_set: &ResultSet) -> String {
+    let rows: Vec<JsonRow> = result_set.rows.iter().map(|row| JsonRow {
+        columns: &result_set.columns,
+        values: row,
+    }).collect();
+
+    serde_json::ser::to_string(&rows).unwrap()
+}
+
+/// A row that serializes to a json object with the keys in column order.
+struct JsonRow<'a> {
+    columns: &'a [String],
+    values: &'a [Option<String>],
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.columns.iter().zip(self.values) {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Quotes a CSV value if it contains a separator, quote or line break.
+fn quote_csv_value(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", value.replace('"', "\"\""));
+    }
+
+    String::from(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_csv, format_json, format_table, ResultSet};
+
+    fn result_set() -> ResultSet {
+        ResultSet {
+            columns: vec![String::from("user_id"), String::from("email")],
+            rows: vec![
+                vec![Some(String::from("admin")), Some(String::from("admin@example.com, \"main\""))],
+                vec![Some(String::from("bob")), None],
+            ],
+            count: 2,
+        }
+    }
+
+    #[test]
+    fn test_if_rows_are_formatted() {
+        assert_eq!(format_table(&result_set()), concat!(
+            "user_id | email\n",
+            "--------+--------------------------\n",
+            "admin   | admin@example.com, \"main\"\n",
+            "bob     |\n",
+            "(2 rows)\n",
+        ));
+
+        assert_eq!(format_json(&result_set()), "[{\"user_id\":\"admin\",\"email\":\"admin@example.com, \\\"main\\\"\"},{\"user_id\":\"bob\",\"email\":null}]");
+        assert_eq!(format_csv(&result_set()), "user_id,email\r\nadmin,\"admin@example.com, \"\"main\"\"\"\r\nbob,\r\n");
+    }
+}
@@ -1,15 +1,18 @@
-use std::{collections::HashMap, process::exit};
+use std::{collections::HashMap, net::{TcpStream, ToSocketAddrs}, process::exit, time::{Duration, Instant}};
 
 use clap::ArgMatches;
-use crate::{config::get_config_properties, utils::{print_as_lines_with_context, print_error}};
+use simple_error::SimpleError;
+use crate::{config::{get_config_properties, parse_jdbc_url}, utils::{print_as_lines_with_context, print_error, redact}};
 use crate::system::get_system_values;
-use crate::utils::print_as_json;
+use crate::utils::{print_as_json, redact_map};
 use crate::service::get_service_status;
 
 const MAGICINFO_INFO_ELEMS: &[&str] = &["wsrm.premiumVersion", "web_url", "CONTENTS_HOME"];
 const DATABASE_INFO_ELEMS: &[&str] = &["wsrm.dbVendor", "wsrm.url", "wsrm.username", "wsrm.password"];
+const JDBC_INFO_ELEMS: &[&str] = &["vendor", "host", "instance", "port", "database", "parameters"];
+const CHECK_INFO_ELEMS: &[&str] = &["reachable", "latencyMs", "vendorMatches", "checkError"];
 const SYSTEM_INFO_ELEMS: &[&str] = &["hwunique", "boardid", "macaddress", "ipaddress"];
-const SERVICE_INFO_ELEMS: &[&str] = &["state", "serviceUser", "startMode"];
+const SERVICE_INFO_ELEMS: &[&str] = &["serviceName", "state", "serviceUser", "startMode"];
 
 #[derive(Debug, Serialize)]
 pub struct AllInfo {
@@ -22,7 +25,7 @@ pub struct AllInfo {
 /// Handles all info related commands.
 pub fn handle_info_command(submatches: &ArgMatches) {
     match submatches.subcommand() {
-        Some(("database", subsubmatches)) => print_config_based_properties(subsubmatches, DATABASE_INFO_ELEMS),
+        Some(("database", subsubmatches)) => print_database_properties(subsubmatches),
         Some(("magicinfo", subsubmatches)) => print_config_based_properties(subsubmatches, MAGICINFO_INFO_ELEMS),
         Some(("system", subsubmatches)) => {
             let system_properties = get_system_values(SYSTEM_INFO_ELEMS);
@@ -64,8 +67,8 @@ fn print_all_info_as_lines() {
     print_as_lines_with_context(magicinfo_props, MAGICINFO_INFO_ELEMS, Some(20));
 
     println!("Database:");
-    let database_props = get_config_values(DATABASE_INFO_ELEMS);
-    print_as_lines_with_context(database_props, DATABASE_INFO_ELEMS, Some(20));
+    let (database_props, _) = get_database_values(None);
+    print_as_lines_with_context(database_props, &[DATABASE_INFO_ELEMS, JDBC_INFO_ELEMS].concat(), Some(20));
 
     println!("System:");
     let system_props = get_system_info();
@@ -79,8 +82,8 @@ fn print_all_info_as_lines() {
 /// Prints all information as a json object
 fn print_all_info_as_json() {
     let all_info = AllInfo{
-        magicinfo: get_config_values(MAGICINFO_INFO_ELEMS),
-        database: get_config_values(DATABASE_INFO_ELEMS),
+        magicinfo: redact_map(get_config_values(MAGICINFO_INFO_ELEMS)),
+        database: redact_map(get_database_values(None).0),
         service: get_service_status(),
         system: get_system_info(),
     };
@@ -99,6 +102,101 @@ fn print_config_based_properties(subsubmatches: &ArgMatches, properties: &[&str]
     print_as_lines_with_context(property_values, properties, None);
 }
 
+/// Prints the database properties and the parts of the JDBC URL, and checks
+/// the connection if requested. Exits with a non-zero exit code if the check
+/// fails.
+fn print_database_properties(subsubmatches: &ArgMatches) {
+    let timeout = subsubmatches.get_flag("check").then(|| Duration::from_secs(*subsubmatches.get_one::<u64>("timeout").unwrap()));
+    let (property_values, check_passed) = get_database_values(timeout);
+
+    if subsubmatches.get_flag("json") {
+        print_as_json(property_values);
+    } else {
+        let mut properties = [DATABASE_INFO_ELEMS, JDBC_INFO_ELEMS].concat();
+        if timeout.is_some() {
+            properties.extend(CHECK_INFO_ELEMS);
+        }
+        print_as_lines_with_context(property_values, &properties, None);
+    }
+
+    if !check_passed {
+        exit(1);
+    }
+}
+
+/// Returns the database properties together with the parts of the JDBC URL.
+/// With a timeout, it also checks whether the database accepts connections
+/// within that time and whether the vendor matches the URL. Returns whether
+/// the check passed.
+fn get_database_values(timeout: Option<Duration>) -> (HashMap<String, Option<String>>, bool) {
+    let mut values = get_config_values(DATABASE_INFO_ELEMS);
+    // A missing url is already visible in the output, so only a url that
+    // can't be parsed is reported.
+    let url_res = values.get("wsrm.url").cloned().flatten()
+        .ok_or_else(|| SimpleError::new("The database url is not set in the config.properties file"))
+        .and_then(|url| parse_jdbc_url(&url).map_err(|e| {
+            print_error(format!("Could not parse the database url: {e}"));
+            e
+        }));
+
+    let url = match url_res {
+        Ok(url) => url,
+        Err(e) => {
+            for key in JDBC_INFO_ELEMS {
+                values.insert(String::from(*key), None);
+            }
+            if timeout.is_some() {
+                for key in CHECK_INFO_ELEMS {
+                    values.insert(String::from(*key), None);
+                }
+                values.insert(String::from("checkError"), Some(e.to_string()));
+            }
+            return (values, timeout.is_none());
+        },
+    };
+
+    let parameters: Vec<String> = url.parameters.iter().map(|(key, value)| format!("{key}={}", redact(key, value))).collect();
+    values.insert(String::from("vendor"), Some(String::from(url.vendor)));
+    values.insert(String::from("host"), Some(url.host.clone()));
+    values.insert(String::from("instance"), url.instance.clone());
+    values.insert(String::from("port"), Some(url.port.to_string()));
+    values.insert(String::from("database"), url.database.clone());
+    values.insert(String::from("parameters"), Some(parameters.join(";")));
+
+    let Some(timeout) = timeout else {
+        return (values, true);
+    };
+
+    let vendor_matches = values.get("wsrm.dbVendor").cloned().flatten().is_some_and(|vendor| vendor.eq_ignore_ascii_case(url.vendor));
+    let connection_res = check_connection(&url.host, url.port, timeout);
+    values.insert(String::from("vendorMatches"), Some(vendor_matches.to_string()));
+    values.insert(String::from("reachable"), Some(connection_res.is_ok().to_string()));
+    values.insert(String::from("latencyMs"), connection_res.as_ref().ok().map(|latency| format!("{:.1}", latency.as_secs_f64() * 1000.0)));
+    values.insert(String::from("checkError"), connection_res.as_ref().err().map(|e| e.to_string()));
+
+    let check_passed = vendor_matches && connection_res.is_ok();
+    (values, check_passed)
+}
+
+/// Opens a TCP connection to the host and returns how long it took.
+fn check_connection(host: &str, port: u16, timeout: Duration) -> Result<Duration, SimpleError> {
+    let addresses = (host, port).to_socket_addrs().map_err(|e| {
+        let error_message = format!("Could not resolve {host}: {e}");
+        SimpleError::new(error_message)
+    })?;
+
+    let mut last_error = SimpleError::new(format!("Could not resolve {host}"));
+    for address in addresses {
+        let start = Instant::now();
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(_) => return Ok(start.elapsed()),
+            Err(e) => last_error = SimpleError::new(format!("Could not connect to {address}: {e}")),
+        }
+    }
+
+    Err(last_error)
+}
+
 /// Returns a hashmap containing the property with a resolved value.
 fn get_config_values(properties: &[&str]) -> HashMap<String, Option<String>> {
     let properties_res = get_config_properties(properties);
@@ -0,0 +1,161 @@
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}, process::exit, sync::OnceLock};
+use clap::ArgMatches;
+use simple_error::SimpleError;
+
+use crate::service::ProbeUrl;
+use crate::utils::print_error;
+
+/// The name of the service that runs MagicINFO by default.
+pub const DEFAULT_SERVICE_NAME: &str = "MagicInfoPremium";
+
+/// The instances file that is used if it's next to the executable and no
+/// other file is given.
+const DEFAULT_INSTANCES_FILE: &str = "magicutil-instances.toml";
+
+static SELECTED_INSTANCE: OnceLock<Instance> = OnceLock::new();
+
+/// A named profile in the instances file. Values that aren't set fall back to
+/// the defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    pub service_name: Option<String>,
+
+    /// The MagicINFO home directory, used instead of MAGICINFO_PREMIUM_HOME.
+    pub home: Option<PathBuf>,
+
+    /// The url or `host:port` on which the availability is probed.
+    pub probe: Option<String>,
+}
+
+/// The MagicINFO installation all commands work on.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub service_name: String,
+
+    /// The home directory, or None to use MAGICINFO_PREMIUM_HOME.
+    pub home: Option<PathBuf>,
+
+    /// The url of the probe, or None to derive it from the configuration.
+    pub probe: Option<String>,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            service_name: String::from(DEFAULT_SERVICE_NAME),
+            home: None,
+            probe: None,
+        }
+    }
+}
+
+/// Selects the instance from the global `--instance` and `--service-name`
+/// arguments. Exits if the profile can't be found.
+pub fn select_instance(matches: &ArgMatches) {
+    let instance_res = get_selected_instance(matches);
+    if let Err(e) = instance_res {
+        print_error(e);
+        exit(1);
+    }
+
+    let _ = SELECTED_INSTANCE.set(instance_res.unwrap());
+}
+
+/// Returns the selected instance, or the default installation if none is
+/// selected.
+pub fn get_instance() -> &'static Instance {
+    SELECTED_INSTANCE.get_or_init(Instance::default)
+}
+
+fn get_selected_instance(matches: &ArgMatches) -> Result<Instance, SimpleError> {
+    let mut instance = Instance::default();
+
+    if let Some(name) = matches.get_one::<String>("instance") {
+        let path = get_instances_path(matches).ok_or_else(|| {
+            let message = format!("Could not find an instances file for instance '{name}'. Use --instances-file, MAGICUTIL_INSTANCES or put {DEFAULT_INSTANCES_FILE} next to magicutil");
+            SimpleError::new(message)
+        })?;
+
+        let mut profiles = read_profiles(&path)?;
+        let profile = profiles.remove(name).ok_or_else(|| {
+            let names: Vec<&str> = profiles.keys().map(|name| name.as_str()).collect();
+            let message = format!("Unknown instance '{name}' in {}, expected one of {}", path.display(), names.join(", "));
+            SimpleError::new(message)
+        })?;
+
+        instance.service_name = profile.service_name.unwrap_or(instance.service_name);
+        instance.home = profile.home;
+        instance.probe = profile.probe;
+    }
+
+    // The service name can be given without a profile, or override the one of
+    // the profile.
+    if let Some(service_name) = matches.get_one::<String>("service-name") {
+        instance.service_name = service_name.clone();
+    }
+
+    Ok(instance)
+}
+
+/// Returns the instances file from the arguments, or the one next to the
+/// executable if it exists.
+fn get_instances_path(matches: &ArgMatches) -> Option<PathBuf> {
+    if let Some(path) = matches.get_one::<String>("instances-file").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+
+    let exe_path = env::current_exe().ok()?;
+    Some(exe_path.with_file_name(DEFAULT_INSTANCES_FILE)).filter(|path| path.exists())
+}
+
+/// Reads the profiles from an instances file, in toml, yaml or json.
+pub fn read_profiles(path: &Path) -> Result<BTreeMap<String, Profile>, SimpleError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        let message = format!("Could not read the instances file {}: {e}", path.display());
+        SimpleError::new(message)
+    })?;
+
+    let is_toml = path.extension().is_none_or(|extension| extension.eq_ignore_ascii_case("toml"));
+    let profiles_res: Result<BTreeMap<String, Profile>, String> = if is_toml {
+        toml::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        serde_yaml::from_str(&content).map_err(|e| e.to_string())
+    };
+
+    let profiles = profiles_res.map_err(|e| {
+        let message = format!("Could not parse the instances file {}: {e}", path.display());
+        SimpleError::new(message)
+    })?;
+
+    for (name, profile) in &profiles {
+        if let Some(probe) = &profile.probe {
+            ProbeUrl::parse(probe).map_err(|e| SimpleError::new(format!("Invalid probe for instance '{name}': {e}")))?;
+        }
+    }
+
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+    use super::read_profiles;
+
+    #[test]
+    fn test_if_profiles_are_read() {
+        let path = env::temp_dir().join(format!("magicutil-instances-{}.toml", std::process::id()));
+        fs::write(&path, "[staging]\nservice-name = \"MagicInfoStaging\"\nhome = \"D:/MagicInfo Staging\"\nprobe = \"127.0.0.1:7011\"\n\n[production]\n").unwrap();
+        let profiles = read_profiles(&path);
+
+        fs::write(&path, "[staging]\nprobe = \"localhost:http\"\n").unwrap();
+        let invalid_profiles = read_profiles(&path);
+        fs::remove_file(&path).unwrap();
+
+        let profiles = profiles.unwrap();
+        assert_eq!(profiles["staging"].service_name.as_deref(), Some("MagicInfoStaging"));
+        assert_eq!(profiles["staging"].probe.as_deref(), Some("127.0.0.1:7011"));
+        assert_eq!(profiles["production"].home, None);
+        assert!(invalid_profiles.is_err());
+    }
+}
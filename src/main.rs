@@ -9,12 +9,18 @@ mod open;
 mod info;
 mod utils;
 mod bcrypt;
+mod crypto;
+mod db;
+mod instance;
 
 fn main() {
     #[cfg(target_os="windows")]
     let _ = ansi_term::enable_ansi_support();
 
     let matches = cli::match_cli_arguments();
+    utils::set_reveal_secrets(matches.get_flag("reveal"));
+    instance::select_instance(&matches);
+    service::set_service_manager(matches.get_one::<String>("service-manager").unwrap());
 
     match matches.subcommand() {
         Some(("system", submatches)) => system::handle_system_command(submatches),
@@ -24,6 +30,8 @@ fn main() {
         Some(("info", submatches)) => info::handle_info_command(submatches), 
         Some(("service", submatches)) => service::handle_service_command(submatches), 
         Some(("bcrypt", submatches)) => bcrypt::handle_bcrypt_command(submatches),
+        Some(("crypto", submatches)) => crypto::handle_crypto_command(submatches),
+        Some(("db", submatches)) => db::handle_db_command(submatches),
         _ => {
             unreachable!("No valid subcommand found");
         }
@@ -151,7 +151,7 @@ fn find_stdio_log(file: &str, mut home_dir: PathBuf) -> Option<String> {
         }
     }
 
-    if matching_paths.len() < 1 {
+    if matching_paths.is_empty() {
         return None;
     }
 
@@ -160,10 +160,12 @@ fn find_stdio_log(file: &str, mut home_dir: PathBuf) -> Option<String> {
 }
 
 /// Opens the given file in the editor
+// The editor is intentionally left running after magicutil exits.
+#[allow(clippy::zombie_processes)]
 fn open_file(file: &str) {
     let editor = get_editor_path();
     let mut command = Command::new(editor);
-    command.args(&[file]);
+    command.args([file]);
 
     let handle_res = command.spawn();
     if let Err(e) = handle_res {
@@ -177,7 +179,7 @@ fn open_file(file: &str) {
 
 /// Tries to resolve the Notepad++ editor. If not found, it will return the path
 /// to the default notepad editor.
-fn get_editor_path<'a>() -> String {
+fn get_editor_path() -> String {
     // Search in the default Notepad++ installation directory.
     if Path::new(NOTEPAD_PP_PATH).exists() {
         return String::from(NOTEPAD_PP_PATH);
@@ -190,13 +192,13 @@ fn get_editor_path<'a>() -> String {
     }
 
     // Fallback on the default plain Windows editor
-    return String::from(DEFAULT_NOTEPAD_PATH);
+    String::from(DEFAULT_NOTEPAD_PATH)
 }
 
 /// Tries to resolve the Notepad++ editor and returns an option with the path.
 fn resolve_notepad_pp() -> Option<String> {
     let mut command = Command::new("where");
-    command.args(&["notepad++"]);
+    command.args(["notepad++"]);
     let output_res = command.output();
 
     if output_res.is_err() {
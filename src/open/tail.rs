@@ -5,10 +5,7 @@ use crate::utils::print_error;
 
 
 pub fn watch_file(file: &str) -> Result<(), io::Error> {
-    let f = match File::open(file) {
-        Ok(x) => x,
-        Err(err) => return Err(err),
-    };
+    let f = File::open(file)?;
 
     let mut reader = BufReader::new(f);
     let mut line = String::new();
@@ -0,0 +1,83 @@
+use std::sync::OnceLock;
+use simple_error::SimpleError;
+
+use super::mock::MockServiceManager;
+use super::state::ServiceState;
+use super::systemd::Systemd;
+use crate::instance::get_instance;
+
+/// The names of the service managers that can be selected.
+pub const MANAGER_NAMES: &[&str] = &["scm", "systemd", "mock"];
+
+/// The service manager used when none is selected.
+pub const DEFAULT_MANAGER: &str = if cfg!(windows) { "scm" } else { "systemd" };
+
+static SELECTED_MANAGER: OnceLock<String> = OnceLock::new();
+
+/// The state, account and start mode of a service, named like Windows names
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceDetails {
+    pub state: ServiceState,
+    pub user: Option<String>,
+    pub start_mode: Option<String>,
+}
+
+impl ServiceDetails {
+    /// Returns the details of a service that doesn't exist.
+    pub fn not_installed() -> Self {
+        Self {
+            state: ServiceState::NotInstalled,
+            user: None,
+            start_mode: None,
+        }
+    }
+}
+
+/// Queries and controls the service that runs MagicINFO. Actions return as
+/// soon as they are requested, without waiting for the service to get to the
+/// new state.
+pub trait ServiceManager {
+    /// Returns the details of the service. A service that doesn't exist is in
+    /// the NotInstalled state.
+    fn details(&self) -> Result<ServiceDetails, SimpleError>;
+
+    fn start(&self) -> Result<(), SimpleError>;
+
+    fn stop(&self) -> Result<(), SimpleError>;
+
+    /// Requests a restart. Managers that can't restart a service only stop it
+    /// and return false from `restarts_itself`, so the caller starts it again
+    /// once it has stopped.
+    fn restart(&self) -> Result<(), SimpleError>;
+
+    /// Returns whether `restart` starts the service again by itself.
+    fn restarts_itself(&self) -> bool {
+        true
+    }
+
+    fn state(&self) -> Result<ServiceState, SimpleError> {
+        self.details().map(|details| details.state)
+    }
+}
+
+/// Selects the service manager that is returned by `get_service_manager`.
+pub fn set_service_manager(name: &str) {
+    let _ = SELECTED_MANAGER.set(String::from(name));
+}
+
+/// Returns the selected service manager for the service of the selected
+/// instance.
+pub fn get_service_manager() -> Result<Box<dyn ServiceManager>, SimpleError> {
+    let service_name = &get_instance().service_name;
+    let name = SELECTED_MANAGER.get().map(|name| name.as_str()).unwrap_or(DEFAULT_MANAGER);
+    match name {
+        #[cfg(windows)]
+        "scm" => Ok(Box::new(super::scm::WindowsScm::new(service_name))),
+        #[cfg(not(windows))]
+        "scm" => Err(SimpleError::new("The Windows service control manager is only available on Windows")),
+        "systemd" => Ok(Box::new(Systemd::new(service_name))),
+        "mock" => MockServiceManager::from_env().map(|mock| Box::new(mock) as Box<dyn ServiceManager>),
+        _ => Err(SimpleError::new(format!("Unknown service manager '{name}', expected one of {}", MANAGER_NAMES.join(", ")))),
+    }
+}
@@ -0,0 +1,162 @@
+use std::{cell::RefCell, collections::{HashMap, VecDeque}, env};
+use simple_error::SimpleError;
+
+use super::manager::{ServiceDetails, ServiceManager};
+use super::state::ServiceState;
+
+/// The environment variable with the script of the mock service manager.
+pub const MOCK_SCRIPT_ENV: &str = "MAGICUTIL_SERVICE_MOCK";
+
+const ACTIONS: &[&str] = &["start", "stop", "restart"];
+
+/// A service manager without a real service, which goes through the states
+/// of a script. The script consists of `key=value` entries separated by `;`:
+///
+/// - `state`: the states the service is in, separated by commas. Every query
+///   moves to the next state and the last state stays. Defaults to `Stopped`.
+///   `Not Installed` acts like a service that doesn't exist.
+/// - `start`, `stop`, `restart`: the states the service goes through after the
+///   action.
+/// - `fail`: the actions that fail, separated by commas.
+/// - `user` and `mode`: the account and start mode of the service.
+///
+/// For example `state=Stopped;start=Start Pending,Start Pending,Running`.
+pub struct MockServiceManager {
+    states: RefCell<VecDeque<ServiceState>>,
+    transitions: HashMap<&'static str, Vec<ServiceState>>,
+    failing: Vec<String>,
+    user: Option<String>,
+    start_mode: Option<String>,
+}
+
+impl MockServiceManager {
+    /// Reads the script from the MAGICUTIL_SERVICE_MOCK environment variable.
+    pub fn from_env() -> Result<Self, SimpleError> {
+        Self::from_script(&env::var(MOCK_SCRIPT_ENV).unwrap_or_default())
+    }
+
+    pub fn from_script(script: &str) -> Result<Self, SimpleError> {
+        let mut mock = Self {
+            states: RefCell::new(VecDeque::from([ServiceState::Stopped])),
+            transitions: HashMap::from([
+                ("start", vec![ServiceState::StartPending, ServiceState::Running]),
+                ("stop", vec![ServiceState::StopPending, ServiceState::Stopped]),
+                ("restart", vec![ServiceState::StopPending, ServiceState::Stopped, ServiceState::StartPending, ServiceState::Running]),
+            ]),
+            failing: Vec::new(),
+            user: Some(String::from("LocalSystem")),
+            start_mode: Some(String::from("Auto")),
+        };
+
+        for entry in script.split(';').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+            let Some((key, value)) = entry.split_once('=') else {
+                return Err(SimpleError::new(format!("Expected key=value in the service mock script, found '{entry}'")));
+            };
+
+            match (key.trim(), value.trim()) {
+                ("state", states) => {
+                    let states = parse_states(states)?;
+                    if states.is_empty() {
+                        return Err(SimpleError::new("The state of the service mock script can't be empty"));
+                    }
+                    mock.states = RefCell::new(VecDeque::from(states));
+                },
+                ("fail", actions) => mock.failing = list(actions),
+                ("user", user) => mock.user = Some(String::from(user)).filter(|user| !user.is_empty()),
+                ("mode", mode) => mock.start_mode = Some(String::from(mode)).filter(|mode| !mode.is_empty()),
+                (action, states) => {
+                    let Some(action) = ACTIONS.iter().find(|name| **name == action) else {
+                        return Err(SimpleError::new(format!("Unknown key '{action}' in the service mock script")));
+                    };
+                    mock.transitions.insert(action, parse_states(states)?);
+                },
+            }
+        }
+
+        Ok(mock)
+    }
+
+    fn act(&self, action: &str) -> Result<(), SimpleError> {
+        if self.failing.iter().any(|failing| failing == action) {
+            return Err(SimpleError::new(format!("The mock service failed to {action}")));
+        }
+        if self.states.borrow()[0] == ServiceState::NotInstalled {
+            return Err(SimpleError::new("The service is not installed"));
+        }
+
+        let states = self.transitions.get(action).cloned().unwrap_or_default();
+        if !states.is_empty() {
+            *self.states.borrow_mut() = VecDeque::from(states);
+        }
+
+        Ok(())
+    }
+}
+
+impl ServiceManager for MockServiceManager {
+    fn details(&self) -> Result<ServiceDetails, SimpleError> {
+        let mut states = self.states.borrow_mut();
+        let state = match states.len() {
+            1 => states[0],
+            _ => states.pop_front().unwrap(),
+        };
+
+        if state == ServiceState::NotInstalled {
+            return Ok(ServiceDetails::not_installed());
+        }
+
+        Ok(ServiceDetails {
+            state,
+            user: self.user.clone(),
+            start_mode: self.start_mode.clone(),
+        })
+    }
+
+    fn start(&self) -> Result<(), SimpleError> {
+        self.act("start")
+    }
+
+    fn stop(&self) -> Result<(), SimpleError> {
+        self.act("stop")
+    }
+
+    fn restart(&self) -> Result<(), SimpleError> {
+        self.act("restart")
+    }
+}
+
+/// Reads a comma-separated list of state names.
+fn parse_states(value: &str) -> Result<Vec<ServiceState>, SimpleError> {
+    list(value).iter().map(|name| {
+        ServiceState::from_name(name).ok_or_else(|| SimpleError::new(format!("Unknown service state '{name}' in the service mock script")))
+    }).collect()
+}
+
+/// Splits a comma-separated list, ignoring empty items.
+fn list(value: &str) -> Vec<String> {
+    value.split(',').map(|item| String::from(item.trim())).filter(|item| !item.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MockServiceManager;
+    use crate::service::manager::ServiceManager;
+    use crate::service::state::ServiceState::*;
+
+    #[test]
+    fn test_if_the_mock_goes_through_the_scripted_states() {
+        let mock = MockServiceManager::from_script("state=Stopped;start=Start Pending,Start Pending,Running").unwrap();
+        assert_eq!(mock.state().unwrap(), Stopped);
+
+        mock.start().unwrap();
+        let states: Vec<_> = (0..4).map(|_| mock.state().unwrap()).collect();
+        assert_eq!(states, [StartPending, StartPending, Running, Running]);
+
+        let mock = MockServiceManager::from_script("state=Not Installed;fail=stop").unwrap();
+        assert_eq!(mock.state().unwrap(), NotInstalled);
+        assert!(mock.start().is_err());
+        assert!(mock.stop().is_err());
+        assert!(MockServiceManager::from_script("pause=Paused").is_err());
+        assert!(MockServiceManager::from_script("state=Starting").is_err());
+    }
+}
@@ -1,10 +1,25 @@
+mod manager;
+mod mock;
+mod probe;
+#[cfg(windows)]
+mod scm;
 mod service_utils;
+mod state;
+mod systemd;
+mod wait;
 
+pub use manager::{set_service_manager, DEFAULT_MANAGER, MANAGER_NAMES};
+pub use probe::ProbeUrl;
 pub use service_utils::get_service_status;
+pub use wait::parse_backoff;
 
-use std::{process::exit, thread, fmt::Display};
+use std::{process::exit, fmt::Display};
 use serde_json::json;
-use service_utils::{ServiceAction, get_status, service_is_available, act_on_service, wait_until, wait_until_available};
+use manager::{ServiceManager, get_service_manager};
+use service_utils::{ServiceAction, NOT_INSTALLED_EXIT_CODE, get_status, get_probe, act_on_service, exit_not_installed, wait_until, wait_until_available, wait_while, wait_while_pending};
+use probe::Probe;
+use state::ServiceState;
+use wait::Waiter;
 use clap::ArgMatches;
 use colored::*;
 
@@ -14,44 +29,54 @@ use crate::utils::print_error;
 pub fn handle_service_command(submatches: &ArgMatches) {
     match submatches.subcommand() {
         Some(("status", subsubmatches)) => {
-            print_status(subsubmatches.get_flag("json"));
+            print_status(require_service_manager().as_ref(), subsubmatches.get_flag("json"));
         },
         Some(("start", subsubmatches)) => {
-            start_service(subsubmatches.get_flag("available"), subsubmatches.get_flag("silent"));
+            let silent = subsubmatches.get_flag("silent");
+            let probe = subsubmatches.get_flag("available").then(|| get_probe(subsubmatches));
+            start_service(require_service_manager().as_ref(), &Waiter::from_args(subsubmatches, silent), probe.as_ref(), silent);
         },
         Some(("stop", subsubmatches)) => {
-            stop_service(subsubmatches.get_flag("silent"));
+            let silent = subsubmatches.get_flag("silent");
+            stop_service(require_service_manager().as_ref(), &Waiter::from_args(subsubmatches, silent), silent);
         },
         Some(("restart", subsubmatches)) => {
-            restart_service(subsubmatches.get_flag("available"), subsubmatches.get_flag("silent"));
+            let silent = subsubmatches.get_flag("silent");
+            let probe = subsubmatches.get_flag("available").then(|| get_probe(subsubmatches));
+            restart_service(require_service_manager().as_ref(), &Waiter::from_args(subsubmatches, silent), probe.as_ref(), silent);
         },
         Some(("available", subsubmatches)) => {
+            let probe = get_probe(subsubmatches);
+            let check_res = probe.check();
             if subsubmatches.get_flag("json") {
                 println!("{}", json!({
-                    "available": service_is_available(),
+                    "available": check_res.is_ok(),
+                    "url": probe.url.to_string(),
+                    "error": check_res.err().map(|e| e.to_string()),
                 }));
                 return;
             }
-    
-            let output = if service_is_available() {
-                "Available"
-            } else {
-                "Unavailable"
-            };
-    
-            println!("{}", output);
+
+            match check_res {
+                Ok(_) => println!("Available"),
+                Err(e) => {
+                    println!("Unavailable");
+                    eprintln!("{}", e.to_string().dimmed());
+                }
+            }
         },
         Some(("wait", subsubmatches)) => {
+            let silent = subsubmatches.get_flag("silent");
+            let waiter = Waiter::from_args(subsubmatches, silent);
             if subsubmatches.get_flag("running") {
-                println!("{}", "Waiting until the service is running...".dimmed());
-                wait_until("Running");
-                println!("{}", "Service is running!".green());
+                wait_until(require_service_manager().as_ref(), ServiceState::Running, &waiter, "Waiting until the service is running...");
+                print("Service is running!".green(), silent);
             }
 
             if subsubmatches.get_flag("available") {
-                println!("{}", "Waiting until the service is available...".dimmed());
-                wait_until_available();
-                println!("{}", "Service is available!".green());
+                let probe = get_probe(subsubmatches);
+                wait_until_available(&waiter, &probe, "Waiting until the service is available...");
+                print("Service is available!".green(), silent);
             }
         },
         _ => {
@@ -60,83 +85,145 @@ pub fn handle_service_command(submatches: &ArgMatches) {
     }
 }
 
-/// Simply prints the current status of the MagicINFO service.
-fn print_status(json_output: bool) {
+/// Returns the selected service manager, or exits if it can't be used. Every
+/// command builds a single manager, as the mock keeps its state in memory,
+/// and only commands that control the service need one.
+fn require_service_manager() -> Box<dyn ServiceManager> {
+    get_service_manager().unwrap_or_else(|e| {
+        print_error(e);
+        exit(1);
+    })
+}
+
+/// Simply prints the current status of the MagicINFO service. Exits with a
+/// distinct exit code if the service is not installed.
+fn print_status(manager: &dyn ServiceManager, json_output: bool) {
+    let status = get_status(manager);
     if json_output {
         println!("{}", json!({
-            "status": get_status(),
+            "status": status,
         }));
-        return;
+    } else {
+        println!("{}", status);
     }
-    
-    println!("{}", get_status());
-}
 
-/// Starts the MagicINFO service
-fn start_service(await_availability: bool, silent: bool) {
-    let status = get_status();
-    if status != "Stopped" {
-        print_error("The service can only be started if it is currently stopped");
-        exit(1);
+    if status == ServiceState::NotInstalled {
+        exit(NOT_INSTALLED_EXIT_CODE);
     }
+}
 
-    act_on_service(ServiceAction::Start);
+/// Starts the MagicINFO service
+fn start_service(manager: &dyn ServiceManager, waiter: &Waiter, probe: Option<&Probe>, silent: bool) {
+    let message = match settle(manager, waiter, ServiceState::Running) {
+        ServiceState::Stopped => {
+            act_on_service(manager, ServiceAction::Start);
+            "Starting MagicINFO..."
+        },
+        ServiceState::StartPending | ServiceState::ContinuePending => "MagicINFO is already starting...",
+        _ => {
+            print_error("The service can only be started if it is currently stopped");
+            exit(1);
+        }
+    };
 
-    print("Starting MagicINFO...".dimmed(), silent);
-    wait_until("Running");
+    wait_until(manager, ServiceState::Running, waiter, message);
     print("Service is running!".green(), silent);
 
-    if await_availability {
-        print("Waiting for availability...".dimmed(), silent);
-        wait_until_available();
+    if let Some(probe) = probe {
+        wait_until_available(waiter, probe, "Waiting for availability...");
         print("Service is available!".green(), silent);
     }
 }
 
 /// Stops the MagicINFO service
-fn stop_service(silent: bool) {
-    let status = get_status();
-    if status != "Running" {
-        print_error("The service can only be stopped if it is currently running");
-        exit(1);
-    }
-
-    act_on_service(ServiceAction::Stop);
+fn stop_service(manager: &dyn ServiceManager, waiter: &Waiter, silent: bool) {
+    let message = match settle(manager, waiter, ServiceState::Stopped) {
+        ServiceState::Running | ServiceState::Paused => {
+            act_on_service(manager, ServiceAction::Stop);
+            "Stopping MagicINFO..."
+        },
+        ServiceState::StopPending => "MagicINFO is already stopping...",
+        _ => {
+            print_error("The service can only be stopped if it is currently running");
+            exit(1);
+        }
+    };
 
-    print("Stopping MagicINFO...".dimmed(), silent);
-    wait_until("Stopped");
+    wait_until(manager, ServiceState::Stopped, waiter, message);
     print("Service is stopped!".green(), silent);
 }
 
 /// Restarts the MagicINFO service
-fn restart_service(await_availability: bool, silent: bool) {
-    let status = get_status();
-    if status != "Running" {
-        print_error("The service can only be restarted if it is currently running");
-        exit(1);
-    }
-
-    act_on_service(ServiceAction::Restart);
-
-    print("Restarting MagicINFO...".dimmed(), silent);
-
-    // One second delay to allow the system to go from the Running state to the
-    // 'Stop Pending' state.
-    thread::sleep(*service_utils::ONE_SECOND);
-
+fn restart_service(manager: &dyn ServiceManager, waiter: &Waiter, probe: Option<&Probe>, silent: bool) {
+    let message = match settle(manager, waiter, ServiceState::Running) {
+        ServiceState::Running => {
+            act_on_service(manager, ServiceAction::Restart);
+
+            // The restart is only requested, so the service can still be
+            // running for a moment. Managers that can't restart a service
+            // only stopped it, and it is started again once it has stopped.
+            if manager.restarts_itself() {
+                wait_while(manager, ServiceState::Running, waiter, "Stopping MagicINFO...");
+            } else {
+                wait_until(manager, ServiceState::Stopped, waiter, "Stopping MagicINFO...");
+                act_on_service(manager, ServiceAction::Start);
+            }
+            "Starting MagicINFO..."
+        },
+        // A service that was stopping, or had already stopped, only has to be
+        // started again.
+        ServiceState::Stopped => {
+            act_on_service(manager, ServiceAction::Start);
+            "Starting MagicINFO..."
+        },
+        ServiceState::StartPending | ServiceState::ContinuePending => "MagicINFO is already starting...",
+        _ => {
+            print_error("The service can only be restarted if it is running or stopped");
+            exit(1);
+        }
+    };
 
-    wait_until("Running");
+    wait_until(manager, ServiceState::Running, waiter, message);
     print("Service is running!".green(), silent);
 
-    if await_availability {
-        print("Waiting for availability...".dimmed(), silent);
-        wait_until_available();
+    if let Some(probe) = probe {
+        wait_until_available(waiter, probe, "Waiting for availability...");
         print("Service is available!".green(), silent);
     }
 }
 
+/// Returns the current state of the service. If the service is changing to
+/// another state than the target, this waits until it got there first.
+fn settle(manager: &dyn ServiceManager, waiter: &Waiter, target: ServiceState) -> ServiceState {
+    let status = get_status(manager);
+    match status {
+        ServiceState::NotInstalled => exit_not_installed(),
+        _ if status.is_pending() && status.settled() != target => {
+            let message = format!("Waiting until the service is no longer {}...", status.name().to_lowercase());
+            wait_while_pending(manager, waiter, &message)
+        },
+        _ => status,
+    }
+}
+
 fn print(msg: impl Display, silent: bool) {
     if !silent {
         println!("{}", msg);
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{restart_service, ServiceManager, ServiceState, Waiter};
+    use super::mock::MockServiceManager;
+
+    #[test]
+    fn test_if_a_stopping_service_is_restarted() {
+        let manager = MockServiceManager::from_script("state=Stop Pending,Stop Pending,Stopped").unwrap();
+        let waiter = Waiter::new(Some(Duration::from_secs(10)), Duration::from_millis(1), 1.0, Duration::from_millis(1), true);
+
+        restart_service(&manager, &waiter, None, true);
+        assert_eq!(manager.state().unwrap(), ServiceState::Running);
+    }
 }
\ No newline at end of file
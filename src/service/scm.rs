@@ -0,0 +1,106 @@
+use std::ffi::OsStr;
+use simple_error::SimpleError;
+use windows_service::{
+    Error,
+    service::{Service, ServiceAccess, ServiceStartType, ServiceState as ScmState},
+    service_manager::{ServiceManager as ServiceControlManager, ServiceManagerAccess},
+};
+
+use super::manager::{ServiceDetails, ServiceManager};
+use super::state::ServiceState;
+
+/// The Windows error code for a service that doesn't exist.
+const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+
+/// Manages the service with the Windows service control manager.
+pub struct WindowsScm {
+    name: String,
+}
+
+impl WindowsScm {
+    pub fn new(name: &str) -> Self {
+        Self { name: String::from(name) }
+    }
+
+    /// Opens the service with the given access, or returns None if it doesn't
+    /// exist.
+    fn open(&self, access: ServiceAccess) -> Result<Option<Service>, SimpleError> {
+        let manager = ServiceControlManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT).map_err(|e| scm_error("connect to the service control manager", e))?;
+        match manager.open_service(&self.name, access) {
+            Ok(service) => Ok(Some(service)),
+            Err(Error::Winapi(e)) if e.raw_os_error() == Some(ERROR_SERVICE_DOES_NOT_EXIST) => Ok(None),
+            Err(e) => Err(scm_error("open the service", e)),
+        }
+    }
+
+    /// Opens the service, failing if it doesn't exist.
+    fn open_existing(&self, access: ServiceAccess) -> Result<Service, SimpleError> {
+        self.open(access)?.ok_or_else(|| SimpleError::new(format!("The service {} is not installed", self.name)))
+    }
+}
+
+impl ServiceManager for WindowsScm {
+    fn details(&self) -> Result<ServiceDetails, SimpleError> {
+        let Some(service) = self.open(ServiceAccess::QUERY_STATUS | ServiceAccess::QUERY_CONFIG)? else {
+            return Ok(ServiceDetails::not_installed());
+        };
+
+        let status = service.query_status().map_err(|e| scm_error("query the service status", e))?;
+        let config = service.query_config().map_err(|e| scm_error("query the service configuration", e))?;
+
+        let start_mode = match config.start_type {
+            ServiceStartType::AutoStart => "Auto",
+            ServiceStartType::OnDemand => "Manual",
+            ServiceStartType::Disabled => "Disabled",
+            ServiceStartType::SystemStart => "System",
+            ServiceStartType::BootStart => "Boot",
+        };
+
+        Ok(ServiceDetails {
+            state: to_service_state(status.current_state),
+            user: config.account_name.map(|account_name| account_name.to_string_lossy().into_owned()),
+            start_mode: Some(String::from(start_mode)),
+        })
+    }
+
+    fn start(&self) -> Result<(), SimpleError> {
+        let service = self.open_existing(ServiceAccess::START)?;
+        service.start(&[] as &[&OsStr]).map_err(|e| scm_error("start the service", e))
+    }
+
+    fn stop(&self) -> Result<(), SimpleError> {
+        let service = self.open_existing(ServiceAccess::STOP)?;
+        service.stop().map(|_| ()).map_err(|e| scm_error("stop the service", e))
+    }
+
+    /// The service control manager can't restart a service, so this only
+    /// stops it. The service is started again once it has stopped.
+    fn restart(&self) -> Result<(), SimpleError> {
+        self.stop()
+    }
+
+    fn restarts_itself(&self) -> bool {
+        false
+    }
+}
+
+fn to_service_state(state: ScmState) -> ServiceState {
+    match state {
+        ScmState::Stopped => ServiceState::Stopped,
+        ScmState::StartPending => ServiceState::StartPending,
+        ScmState::StopPending => ServiceState::StopPending,
+        ScmState::Running => ServiceState::Running,
+        ScmState::ContinuePending => ServiceState::ContinuePending,
+        ScmState::PausePending => ServiceState::PausePending,
+        ScmState::Paused => ServiceState::Paused,
+    }
+}
+
+/// Describes an error of the service control manager. The Windows error is
+/// shown instead of the generic description of a Winapi error.
+fn scm_error(action: &str, e: Error) -> SimpleError {
+    match e {
+        Error::Winapi(io_error) => SimpleError::new(format!("Could not {action}: {io_error}")),
+        e => SimpleError::new(format!("Could not {action}: {e}")),
+    }
+}
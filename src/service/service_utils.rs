@@ -1,13 +1,15 @@
-use std::{collections::HashMap, net::TcpStream, process::{Command, Stdio, exit}, thread, time, io::{Write, Read}};
+use std::{collections::HashMap, process::exit};
+use clap::ArgMatches;
 
-use crate::utils::{get_wmic_output_as_list, print_error};
+use super::manager::{ServiceManager, get_service_manager};
+use super::probe::Probe;
+use super::state::ServiceState;
+use super::wait::Waiter;
+use crate::instance::get_instance;
+use crate::utils::print_error;
 
-lazy_static! {
-    pub static ref ONE_SECOND: time::Duration = time::Duration::from_secs(1);
-}
-
-const REQUEST_BYTES: &[u8] = b"GET /MagicInfo/openapi/auth?cmd=isMagicInfo HTTP/1.0\r\n\r\n";
-const RESPONSE_SUCCESS_BYTES: &[u8] = b"HTTP/1.1 200";
+/// The exit code when the service doesn't exist.
+pub const NOT_INSTALLED_EXIT_CODE: i32 = 3;
 
 pub enum ServiceAction {
     Start,
@@ -15,134 +17,123 @@ pub enum ServiceAction {
     Restart
 }
 
-/// Converts a service action to the corresponding Powershell command.
-pub fn action_to_command(action: ServiceAction) -> &'static str {
-    match action {
-        ServiceAction::Start => "Start-Service",
-        ServiceAction::Stop => "Stop-Service",
-        ServiceAction::Restart => "Restart-Service",
-    }
-}
-
 /// Executes the given action on the service
-pub fn act_on_service(action: ServiceAction) {
-    let action_cmd = action_to_command(action);
-
-    let mut command = Command::new("powershell");
-    command.args(&["-c", action_cmd, "MagicInfoPremium"]);
-
-    command.stdout(Stdio::null());
-    command.stderr(Stdio::null());
-
-    let handle_res = command.spawn();
-    if let Err(e) = handle_res {
+pub fn act_on_service(manager: &dyn ServiceManager, action: ServiceAction) {
+    let action_res = match action {
+        ServiceAction::Start => manager.start(),
+        ServiceAction::Stop => manager.stop(),
+        ServiceAction::Restart => manager.restart(),
+    };
+
+    if let Err(e) = action_res {
         print_error(e);
         exit(1);
     }
 }
 
-/// Loops and sleeps until the service is available
-pub fn wait_until_available() {
-    loop {
-        if service_is_available() {
-            return;
-        }
-        thread::sleep(*ONE_SECOND);
-    }
+/// Polls until the probe gets the expected response. Shows why the service is
+/// not available yet, like a certificate that can't be verified.
+pub fn wait_until_available(waiter: &Waiter, probe: &Probe, message: &str) {
+    let goal = format!("{} is available", probe.url);
+    waiter.wait_for_result(message, &goal, || probe.check());
 }
 
-/// Tests if the service is available by checking if port 7001 on localhost
-/// at path /MagicInfo/openapi/auth?cmd=isMagicInfo returns a 200 response.
-pub fn service_is_available() -> bool {
-    let connection_res = TcpStream::connect("127.0.0.1:7001");
-    if let Err(_e) = connection_res {
-        return false;
+/// Returns the probe for the arguments, or exits if the probe url is invalid.
+pub fn get_probe(submatches: &ArgMatches) -> Probe {
+    let probe_res = Probe::from_args(submatches);
+    if let Err(e) = probe_res {
+        print_error(e);
+        exit(1);
     }
 
-    let mut connection = connection_res.unwrap();
-    if let Err(_e) = connection.set_read_timeout(Some(*ONE_SECOND)) {
-        return false;
-    }
+    probe_res.unwrap()
+}
 
-    let write_res = connection.write_all(REQUEST_BYTES);
-    if let Err(_e) = write_res {
-        return false;
-    }
+/// Watches the state of the MagicINFO service and returns only if the state
+/// matches the one required as the parameter. Exits if the service turns out
+/// not to be installed, or if it was on its way to the state but ended up in
+/// another one, like a start that fails.
+pub fn wait_until(manager: &dyn ServiceManager, state: ServiceState, waiter: &Waiter, message: &str) {
+    let goal = format!("the service is {}", state.name().to_lowercase());
+    let mut heading_to_state = false;
+    waiter.wait_for(message, &goal, || {
+        let status = get_status(manager);
+        if status == state {
+            return Some(());
+        }
 
-    let mut buffer = [0; 128];
-    let read_res = connection.read(&mut buffer);
-    if let Err(_e) = read_res {
-        return false;
-    }
+        if status == ServiceState::NotInstalled {
+            exit_not_installed();
+        }
+        if status.is_pending() {
+            heading_to_state = status.settled() == state;
+        } else if heading_to_state {
+            print_error(format!("The service is {status} instead of {state}"));
+            exit(1);
+        }
 
-    let read_size = read_res.unwrap();
-    let output = &buffer[..read_size];
+        None
+    });
+}
 
-    // Checks if the response starts with: HTTP/1.1 200
-    if !output.starts_with(RESPONSE_SUCCESS_BYTES) {
-        return false;
-    }
+/// Waits until the service has left the given state, like a running service
+/// that was asked to restart. Exits if the service turns out not to be
+/// installed.
+pub fn wait_while(manager: &dyn ServiceManager, state: ServiceState, waiter: &Waiter, message: &str) {
+    let goal = format!("the service is no longer {}", state.name().to_lowercase());
+    waiter.wait_for(message, &goal, || {
+        let status = get_status(manager);
+        if status == ServiceState::NotInstalled {
+            exit_not_installed();
+        }
 
-    true
+        (status != state).then_some(())
+    });
 }
 
-/// Watches the state of the MagicINFO service and returns only if the state
-/// matches the one required as the parameter.
-pub fn wait_until(state: &str) {
-    loop {
-        let status = get_status();
-        if status == state {
-            return;
+/// Waits until the service is no longer in a pending state and returns the
+/// state it ended up in.
+pub fn wait_while_pending(manager: &dyn ServiceManager, waiter: &Waiter, message: &str) -> ServiceState {
+    waiter.wait_for(message, "the service is no longer pending", || {
+        let status = get_status(manager);
+        if status == ServiceState::NotInstalled {
+            exit_not_installed();
         }
 
-        thread::sleep(*ONE_SECOND);
-    }
+        Some(status).filter(|status| !status.is_pending())
+    })
 }
 
 /// Returns the current status of the MagicINFO service
-pub fn get_status() -> String {
-    let mut command = Command::new("wmic");
-    command.args(&["Service", "WHERE", "name='MagicInfoPremium'", "GET", "State"]);
-    let output_res = command.output();
-    if let Err(e) = output_res {
+pub fn get_status(manager: &dyn ServiceManager) -> ServiceState {
+    let state_res = manager.state();
+    if let Err(e) = state_res {
         print_error(e);
         exit(1);
     }
-    
 
-    let status_output = output_res.unwrap().stdout;
-    let output_split = get_wmic_output_as_list(status_output);
+    state_res.unwrap()
+}
 
-    output_split[0].clone()
+/// Exits with the exit code for a service that doesn't exist.
+pub fn exit_not_installed() -> ! {
+    print_error(format!("The {} service is not installed", get_instance().service_name));
+    exit(NOT_INSTALLED_EXIT_CODE);
 }
 
 /// Returns information about the service that runs MagicINFO.
 pub fn get_service_status() -> HashMap<String, Option<String>> {
-    let mut command = Command::new("wmic");
-    command.args(&["Service", "WHERE", "name='MagicInfoPremium'", "GET", "State,StartName,StartMode"]);
-    
+    let details = get_service_manager()
+        .and_then(|manager| manager.details())
+        .ok()
+        .filter(|details| details.state != ServiceState::NotInstalled);
+
+    // If the service doesn't exist or can't be queried, all values are empty.
     let mut property_map = HashMap::new();
-    let output_res = command.output();
-    if output_res.is_err() {
-        property_map.insert(String::from("state"), None);
-        property_map.insert(String::from("serviceUser"), None);
-        property_map.insert(String::from("startMode"), None);
-    } else {
-        let status_output = output_res.unwrap().stdout;
-        let output_split = get_wmic_output_as_list(status_output);
-
-        // If the service doesn't exist, we receive an empty status_output, resulting
-        // in an empty output_split.
-        if output_split.is_empty() {
-            property_map.insert(String::from("state"), None);
-            property_map.insert(String::from("serviceUser"), None);
-            property_map.insert(String::from("startMode"), None);
-        } else {
-            property_map.insert(String::from("state"), Some(output_split[0].clone()));
-            property_map.insert(String::from("serviceUser"), Some(output_split[1].clone()));
-            property_map.insert(String::from("startMode"), Some(output_split[2].clone()));
-        }
-    }
+    property_map.insert(String::from("serviceName"), Some(get_instance().service_name.clone()));
+    property_map.insert(String::from("state"), details.as_ref().map(|details| details.state.to_string()));
+    property_map.insert(String::from("serviceUser"), details.as_ref().and_then(|details| details.user.clone()));
+    property_map.insert(String::from("startMode"), details.and_then(|details| details.start_mode));
 
     property_map
-}
\ No newline at end of file
+}
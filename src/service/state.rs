@@ -0,0 +1,98 @@
+use std::fmt;
+use serde::{Serialize, Serializer};
+
+/// The state of a Windows service, plus the state of a service that doesn't
+/// exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceState {
+    Stopped,
+    StartPending,
+    StopPending,
+    Running,
+    ContinuePending,
+    PausePending,
+    Paused,
+    NotInstalled,
+}
+
+const STATES: &[ServiceState] = &[
+    ServiceState::Stopped,
+    ServiceState::StartPending,
+    ServiceState::StopPending,
+    ServiceState::Running,
+    ServiceState::ContinuePending,
+    ServiceState::PausePending,
+    ServiceState::Paused,
+    ServiceState::NotInstalled,
+];
+
+impl ServiceState {
+    /// Returns the name of the state as Windows shows it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServiceState::Stopped => "Stopped",
+            ServiceState::StartPending => "Start Pending",
+            ServiceState::StopPending => "Stop Pending",
+            ServiceState::Running => "Running",
+            ServiceState::ContinuePending => "Continue Pending",
+            ServiceState::PausePending => "Pause Pending",
+            ServiceState::Paused => "Paused",
+            ServiceState::NotInstalled => "Not Installed",
+        }
+    }
+
+    /// Returns the state with the given name. Case, spaces, dashes and
+    /// underscores are ignored, so `StartPending` and `start-pending` work too.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let normalize = |name: &str| name.chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_lowercase();
+
+        let name = normalize(name);
+        STATES.iter().copied().find(|state| normalize(state.name()) == name)
+    }
+
+    /// Returns whether the service is changing to another state.
+    pub fn is_pending(&self) -> bool {
+        matches!(self, ServiceState::StartPending | ServiceState::StopPending | ServiceState::ContinuePending | ServiceState::PausePending)
+    }
+
+    /// Returns the state a pending state ends in. Other states are returned as
+    /// they are.
+    pub fn settled(&self) -> Self {
+        match self {
+            ServiceState::StartPending | ServiceState::ContinuePending => ServiceState::Running,
+            ServiceState::StopPending => ServiceState::Stopped,
+            ServiceState::PausePending => ServiceState::Paused,
+            state => *state,
+        }
+    }
+}
+
+impl fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for ServiceState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServiceState;
+
+    #[test]
+    fn test_if_states_are_read_by_name() {
+        assert_eq!(ServiceState::from_name("Start Pending"), Some(ServiceState::StartPending));
+        assert_eq!(ServiceState::from_name("stop-pending"), Some(ServiceState::StopPending));
+        assert_eq!(ServiceState::from_name("NotInstalled"), Some(ServiceState::NotInstalled));
+        assert_eq!(ServiceState::from_name("Starting"), None);
+        assert_eq!(ServiceState::ContinuePending.settled(), ServiceState::Running);
+        assert!(!ServiceState::Paused.is_pending());
+    }
+}
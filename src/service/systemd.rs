@@ -0,0 +1,105 @@
+use std::process::Command;
+use simple_error::SimpleError;
+
+use super::manager::{ServiceDetails, ServiceManager};
+use super::state::ServiceState;
+
+const SHOW_PROPERTIES: &str = "LoadState,ActiveState,User,UnitFileState";
+
+/// Manages the service as a systemd unit with `systemctl`.
+pub struct Systemd {
+    unit: String,
+}
+
+impl Systemd {
+    pub fn new(unit: &str) -> Self {
+        Self { unit: String::from(unit) }
+    }
+
+    /// Runs systemctl and returns its output, or its error message if it
+    /// fails.
+    fn systemctl(&self, args: &[&str]) -> Result<String, SimpleError> {
+        let output = Command::new("systemctl").args(args).arg("--").arg(&self.unit).output().map_err(|e| {
+            let message = format!("Could not run systemctl: {e}");
+            SimpleError::new(message)
+        })?;
+
+        if !output.status.success() {
+            let message = format!("systemctl {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim());
+            return Err(SimpleError::new(message));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl ServiceManager for Systemd {
+    fn details(&self) -> Result<ServiceDetails, SimpleError> {
+        let output = self.systemctl(&["show", "--property", SHOW_PROPERTIES])?;
+        Ok(parse_show_output(&output))
+    }
+
+    fn start(&self) -> Result<(), SimpleError> {
+        self.systemctl(&["start", "--no-block"]).map(|_| ())
+    }
+
+    fn stop(&self) -> Result<(), SimpleError> {
+        self.systemctl(&["stop", "--no-block"]).map(|_| ())
+    }
+
+    fn restart(&self) -> Result<(), SimpleError> {
+        self.systemctl(&["restart", "--no-block"]).map(|_| ())
+    }
+}
+
+/// Converts the properties printed by `systemctl show` to the details of the
+/// service, using the names Windows uses for start modes.
+fn parse_show_output(output: &str) -> ServiceDetails {
+    let property = |name: &str| output.lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.trim());
+
+    if matches!(property("LoadState"), None | Some("not-found")) {
+        return ServiceDetails::not_installed();
+    }
+
+    let state = match property("ActiveState").unwrap_or_default() {
+        "active" | "reloading" => ServiceState::Running,
+        "activating" => ServiceState::StartPending,
+        "deactivating" => ServiceState::StopPending,
+        _ => ServiceState::Stopped,
+    };
+
+    // Units without a user run as root.
+    let user = property("User").filter(|user| !user.is_empty()).unwrap_or("root");
+
+    let start_mode = property("UnitFileState").map(|unit_file_state| match unit_file_state {
+        "enabled" | "enabled-runtime" | "static" | "alias" | "indirect" | "generated" => "Auto",
+        "masked" | "masked-runtime" => "Disabled",
+        _ => "Manual",
+    });
+
+    ServiceDetails {
+        state,
+        user: Some(String::from(user)),
+        start_mode: start_mode.map(String::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_show_output;
+    use crate::service::state::ServiceState;
+
+    #[test]
+    fn test_if_systemctl_output_is_parsed() {
+        let details = parse_show_output("LoadState=loaded\nActiveState=activating\nUser=\nUnitFileState=enabled\n");
+        assert_eq!((details.state, details.user.as_deref(), details.start_mode.as_deref()), (ServiceState::StartPending, Some("root"), Some("Auto")));
+
+        let details = parse_show_output("LoadState=loaded\nActiveState=failed\nUser=magicinfo\nUnitFileState=disabled\n");
+        assert_eq!((details.state, details.user.as_deref(), details.start_mode.as_deref()), (ServiceState::Stopped, Some("magicinfo"), Some("Manual")));
+
+        assert_eq!(parse_show_output("LoadState=not-found\nActiveState=inactive\n").state, ServiceState::NotInstalled);
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fmt, io::{self, IsTerminal, Write}, process::exit, thread, time::{Duration, Instant}};
+use clap::ArgMatches;
+use colored::*;
+
+use crate::utils::print_error;
+
+/// The exit code when the service didn't get to the expected state in time.
+pub const TIMEOUT_EXIT_CODE: i32 = 4;
+
+/// How often the elapsed time is updated while waiting.
+const TICK: Duration = Duration::from_millis(200);
+
+/// Polls until a condition is met, with a timeout for the whole command and an
+/// interval that grows with every poll if backoff is enabled. Shows the elapsed
+/// time after the message of the current wait, unless silent.
+pub struct Waiter {
+    started_at: Instant,
+    timeout: Option<Duration>,
+    interval: Duration,
+    backoff: f64,
+    max_interval: Duration,
+    silent: bool,
+}
+
+impl Waiter {
+    /// Reads the timeout, interval and backoff from the arguments of a service
+    /// subcommand.
+    pub fn from_args(submatches: &ArgMatches, silent: bool) -> Self {
+        let timeout = submatches.get_one::<u64>("timeout").copied().filter(|timeout| *timeout > 0);
+        let interval = Duration::from_millis(*submatches.get_one::<u64>("interval").unwrap());
+        let max_interval = Duration::from_millis(*submatches.get_one::<u64>("max-interval").unwrap());
+        let backoff = *submatches.get_one::<f64>("backoff").unwrap();
+
+        Self::new(timeout.map(Duration::from_secs), interval, backoff, max_interval, silent)
+    }
+
+    /// Starts the timeout now. The interval never grows beyond the maximum
+    /// interval, which is at least the interval itself.
+    pub fn new(timeout: Option<Duration>, interval: Duration, backoff: f64, max_interval: Duration, silent: bool) -> Self {
+        Self {
+            started_at: Instant::now(),
+            timeout,
+            interval,
+            backoff,
+            max_interval: max_interval.max(interval),
+            silent,
+        }
+    }
+
+    /// Calls `check` until it returns a value. Prints the message first, and
+    /// keeps the elapsed time behind it up to date on a terminal. Exits with
+    /// the timeout exit code if the timeout of the command passes, describing
+    /// what it waited for with the goal.
+    pub fn wait_for<T>(&self, message: &str, goal: &str, mut check: impl FnMut() -> Option<T>) -> T {
+        self.poll(message, goal, || check().ok_or(None))
+    }
+
+    /// Calls `check` until it succeeds, like `wait_for`. Errors are printed
+    /// when they differ from the previous one, and the last error is added to
+    /// the timeout message, so it's clear why the wait takes so long.
+    pub fn wait_for_result<T, E: fmt::Display>(&self, message: &str, goal: &str, mut check: impl FnMut() -> Result<T, E>) -> T {
+        self.poll(message, goal, || check().map_err(|e| Some(e.to_string())))
+    }
+
+    fn poll<T>(&self, message: &str, goal: &str, mut check: impl FnMut() -> Result<T, Option<String>>) -> T {
+        let live = !self.silent && io::stdout().is_terminal();
+        if !self.silent && !live {
+            println!("{}", message.dimmed());
+        }
+
+        let mut interval = self.interval;
+        let mut last_error = None;
+        loop {
+            match check() {
+                Ok(value) => {
+                    self.show_elapsed(message, live, true);
+                    return value;
+                },
+                Err(error) if error != last_error => {
+                    if let Some(error) = &error {
+                        self.show_error(message, error, live);
+                    }
+                    last_error = error;
+                },
+                Err(_) => {},
+            }
+
+            let mut next_check = Instant::now() + interval;
+            if let Some(deadline) = self.deadline() {
+                if Instant::now() >= deadline {
+                    self.show_elapsed(message, live, true);
+                    let reason = last_error.map(|error| format!(": {error}")).unwrap_or_default();
+                    print_error(format!("Timed out after {} waiting until {goal}{reason}", format_elapsed(self.timeout.unwrap())));
+                    exit(TIMEOUT_EXIT_CODE);
+                }
+                next_check = next_check.min(deadline);
+            }
+
+            while Instant::now() < next_check {
+                self.show_elapsed(message, live, false);
+                thread::sleep(TICK.min(next_check - Instant::now()));
+            }
+
+            interval = interval.mul_f64(self.backoff).min(self.max_interval);
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| self.started_at + timeout)
+    }
+
+    /// Rewrites the line with the message and the elapsed time. The line is
+    /// ended when the wait is done.
+    fn show_elapsed(&self, message: &str, live: bool, done: bool) {
+        if !live {
+            return;
+        }
+
+        let elapsed = format_elapsed(self.started_at.elapsed());
+        print!("\r{} {}", message.dimmed(), elapsed.dimmed());
+        if done {
+            println!();
+        }
+        let _ = io::stdout().flush();
+    }
+
+    /// Prints the error below the message, unless silent. On a terminal the
+    /// line with the elapsed time is ended first, and continues below it.
+    fn show_error(&self, message: &str, error: &str, live: bool) {
+        if self.silent {
+            return;
+        }
+
+        self.show_elapsed(message, live, true);
+        eprintln!("{}", error.dimmed());
+    }
+}
+
+/// Parses the backoff factor, which has to be between 1 and 10.
+pub fn parse_backoff(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(backoff) if (1.0..=10.0).contains(&backoff) => Ok(backoff),
+        _ => Err(String::from("expected a number between 1 and 10, like 1.5")),
+    }
+}
+
+/// Formats a duration as seconds, or minutes and seconds.
+fn format_elapsed(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        _ => format!("{}m {:02}s", seconds / 60, seconds % 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::{Duration, Instant}};
+    use super::{format_elapsed, parse_backoff, Waiter};
+
+    #[test]
+    fn test_if_the_interval_backs_off() {
+        let waiter = Waiter {
+            started_at: Instant::now(),
+            timeout: None,
+            interval: Duration::from_millis(10),
+            backoff: 2.0,
+            max_interval: Duration::from_millis(40),
+            silent: true,
+        };
+
+        let checks = Cell::new(0);
+        let value = waiter.wait_for("Waiting...", "the test is done", || {
+            checks.set(checks.get() + 1);
+            Some(checks.get()).filter(|checks| *checks == 5)
+        });
+
+        // 10 + 20 + 40 + 40 milliseconds between the five checks.
+        assert_eq!(value, 5);
+        assert!(waiter.started_at.elapsed() >= Duration::from_millis(110));
+        assert_eq!(format_elapsed(Duration::from_secs(125)), "2m 05s");
+        assert!(parse_backoff("0.5").is_err());
+    }
+}
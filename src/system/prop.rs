@@ -1,7 +1,4 @@
-use mac_address;
-use local_ipaddress;
 use std::process::Command;
-use md5;
 
 const STANDARD_MAC_ADDRESS: &str = "112233AABBCC";
 const STANDARD_BOARD_ID: &str = "MI0020130925";
@@ -48,7 +45,7 @@ pub fn get_board_id() -> String {
 
     // Execute the wmic call to get the board ID.
     let output_res = Command::new("wmic")
-        .args(&["bios", "get", "serialnumber"]).output();
+        .args(["bios", "get", "serialnumber"]).output();
     if output_res.is_err() {
         return String::from(STANDARD_BOARD_ID);
     }
@@ -74,8 +71,8 @@ pub fn get_board_id() -> String {
 /// Returns the local ip address of this system
 pub fn get_ip_address() -> String {
     let ipaddr_opt = local_ipaddress::get();
-    if ipaddr_opt.is_some() {
-        return ipaddr_opt.unwrap();
+    if let Some(ipaddr) = ipaddr_opt {
+        return ipaddr;
     }
 
     String::new()
@@ -1,14 +1,54 @@
-use std::{collections::HashMap, iter::FromIterator, fmt::Display, process::exit};
+use std::{collections::HashMap, fmt::Display, sync::atomic::{AtomicBool, Ordering}};
 use pad::PadStr;
+use regex::Regex;
 
+/// The text that is printed instead of a secret value.
+pub const REDACTED_VALUE: &str = "********";
 
+lazy_static! {
+    /// Matches keys whose values are secret, like passwords, tokens and keys,
+    /// including MagicINFO's encrypt.manager.key.v1.
+    static ref SECRET_KEY_REGEX: Regex = Regex::new(r"(?i)(password|passwd|pwd|secret|token|credential|(^|[._-])(api|private|access)?key([._-]|$))").unwrap();
+}
+
+/// Whether secret values are printed as they are, instead of redacted.
+static REVEAL_SECRETS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether secret values are printed as they are.
+pub fn set_reveal_secrets(reveal: bool) {
+    REVEAL_SECRETS.store(reveal, Ordering::Relaxed);
+}
+
+/// Returns whether the value of the key is a secret.
+pub fn is_secret_key(key: &str) -> bool {
+    SECRET_KEY_REGEX.is_match(key)
+}
+
+/// Returns the value to print for the key. Secret values are redacted, unless
+/// they should be revealed.
+pub fn redact(key: &str, value: &str) -> String {
+    if is_secret_key(key) && !value.is_empty() && !REVEAL_SECRETS.load(Ordering::Relaxed) {
+        return String::from(REDACTED_VALUE);
+    }
+
+    String::from(value)
+}
+
+/// Redacts the secret values in the map.
+pub fn redact_map(data: HashMap<String, Option<String>>) -> HashMap<String, Option<String>> {
+    data.into_iter().map(|(key, value)| {
+        let value = value.map(|value| redact(&key, &value));
+        (key, value)
+    }).collect()
+}
 
 pub fn print_as_json(data: HashMap<String, Option<String>>) {
-    let json = serde_json::ser::to_string(&data).unwrap();
+    let json = serde_json::ser::to_string(&redact_map(data)).unwrap();
     println!("{}", json);
 }
 
 pub fn print_as_lines(data: HashMap<String, Option<String>>, properties: &[&str]) {
+    let data = redact_map(data);
     for property in properties {
         let property_value = data.get(property.to_owned()).unwrap();
         println!("{}", property_value.clone().unwrap_or_default());
@@ -18,16 +58,17 @@ pub fn print_as_lines(data: HashMap<String, Option<String>>, properties: &[&str]
 pub fn print_as_lines_with_context(data: HashMap<String, Option<String>>, properties: &[&str], pad_length: Option<usize>) {
     // Determine the longest length of the properties given.
     let mut longest_property_length: usize = 0;
-    if pad_length.is_none() {
+    if let Some(length) = pad_length {
+        longest_property_length = length;
+    } else {
         for property in properties {
             if property.len() > longest_property_length {
                 longest_property_length = property.len()
             }
         }
-    } else {
-        longest_property_length = pad_length.unwrap();
     }
 
+    let data = redact_map(data);
     for property in properties {
         let property_value = data.get(property.to_owned()).unwrap();
         println!("  {} :  {}", property.pad_to_width(longest_property_length), property_value.clone().unwrap_or_default());
@@ -38,68 +79,22 @@ pub fn print_error<T: Display>(msg: T) {
     eprintln!("magicutil: {}", msg);
 }
 
-pub fn get_wmic_output_as_list(wmic_output: Vec<u8>) -> Vec<String> {
-    let output_string = String::from_utf8(wmic_output).unwrap();
-    let output_option = output_string.split_once("\n");
-
-    if output_option.is_none() {
-        print_error("Could not find the status of the MagicINFO service");
-        exit(1);
-    }
-
-    let output_line = output_option.unwrap().1;
-
-    let mut match_list = Vec::new();
-    let mut current_word: Vec<char> = Vec::new();
-
-    // Iterate over the output letter by letter. Split the output in pairs of
-    // characters separated by at least two spaces.
-    for letter in output_line.chars() {
+#[cfg(test)]
+mod tests {
 
-        // Leading whitespaces are ignored
-        if letter.is_whitespace() && current_word.is_empty() {
-            continue;
-        }
+    #[test]
+    fn test_if_secret_keys_are_redacted() {
+        use super::{is_secret_key, redact, REDACTED_VALUE};
 
-        // If we encounter a whitespace while the last recorded character in the
-        // current_word is also a whitespace, then we know we found a two-space
-        // delimiter.
-        if letter.is_whitespace() && current_word.last().unwrap().is_whitespace() {
-            let match_string = String::from_iter(current_word.clone()).trim().to_owned();
-            match_list.push(match_string);
-            current_word.clear();
-            continue
+        for key in ["wsrm.password", "encrypt.manager.key.v1", "api_key", "jwt.secret", "auth.token", "DB_PASSWD"] {
+            assert!(is_secret_key(key), "{key}");
         }
-
-        current_word.push(letter);
-    }
-
-    // Make sure we also get the last word if it contains something.
-    if !current_word.is_empty() {
-        let match_string = String::from_iter(current_word).trim().to_owned();
-        if !match_string.is_empty() {
-            match_list.push(match_string);
+        for key in ["wsrm.username", "monkey.count", "keystore.path", "web_url"] {
+            assert!(!is_secret_key(key), "{key}");
         }
-    }
-
-    return match_list;
-}
 
-#[cfg(test)]
-mod tests {
-
-    #[test]
-    fn test_if_splitting_wmic_output_works() {
-        use super::get_wmic_output_as_list;
-
-        let output_string = "StartMode  StartName    State  \nAuto       LocalSystem  Running";
-        let output_vector = Vec::from(output_string);
-
-        let wmic_fields = get_wmic_output_as_list(output_vector);
-        println!("{:#?}", wmic_fields);
-        
-        assert_eq!(wmic_fields[0], "Auto");
-        assert_eq!(wmic_fields[1], "LocalSystem");
-        assert_eq!(wmic_fields[2], "Running");
+        assert_eq!(redact("wsrm.password", "secret"), REDACTED_VALUE);
+        assert_eq!(redact("wsrm.password", ""), "");
+        assert_eq!(redact("wsrm.url", "jdbc"), "jdbc");
     }
 }
\ No newline at end of file